@@ -0,0 +1,98 @@
+use bitpack::bitpack::{gets, getu, news, newu};
+use bitpack::bulk::{pack_signed, pack_unsigned, unpack_signed, unpack_unsigned};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+/// Number of words in each benchmark
+const WORDS: usize = 1 << 16;
+
+/// The fields of an rpeg legacy block word as (width, lsb, signed)
+const FIELDS: [(u64, u64, bool); 6] =
+    [(9, 23, false), (5, 18, true), (5, 13, true), (5, 8, true), (4, 4, false), (4, 0, false)];
+
+/// Words with every bit pattern spread evenly, from a simple LCG
+fn words() -> Vec<u64> {
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    (0..WORDS)
+        .map(|_| {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            state >> 32
+        })
+        .collect()
+}
+
+fn unpack(c: &mut Criterion) {
+    let words = words();
+    let mut group = c.benchmark_group("unpack");
+    group.throughput(Throughput::Elements(WORDS as u64));
+
+    group.bench_function("per word", |b| {
+        b.iter(|| {
+            let mut columns: [Vec<i64>; 6] = Default::default();
+            for &word in black_box(&words) {
+                for (column, &(width, lsb, signed)) in columns.iter_mut().zip(&FIELDS) {
+                    let value = if signed { gets(word, width, lsb) } else { getu(word, width, lsb).map(|v| v as i64) };
+                    column.push(value.unwrap_or(0));
+                }
+            }
+            columns
+        })
+    });
+
+    group.bench_function("bulk", |b| {
+        b.iter(|| {
+            FIELDS.map(|(width, lsb, signed)| {
+                if signed {
+                    unpack_signed(black_box(&words), width, lsb).unwrap()
+                } else {
+                    unpack_unsigned(black_box(&words), width, lsb).unwrap().into_iter().map(|v| v as i64).collect()
+                }
+            })
+        })
+    });
+
+    group.finish();
+}
+
+fn pack(c: &mut Criterion) {
+    let words = words();
+    let columns = FIELDS.map(|(width, lsb, _)| unpack_unsigned(&words, width, lsb).unwrap());
+    let signed = FIELDS.map(|(width, lsb, _)| unpack_signed(&words, width, lsb).unwrap());
+    let mut group = c.benchmark_group("pack");
+    group.throughput(Throughput::Elements(WORDS as u64));
+
+    group.bench_function("per word", |b| {
+        b.iter(|| {
+            (0..WORDS)
+                .map(|index| {
+                    FIELDS.iter().enumerate().fold(0, |word, (field, &(width, lsb, is_signed))| {
+                        let word = if is_signed {
+                            news(word, width, lsb, signed[field][index])
+                        } else {
+                            newu(word, width, lsb, columns[field][index])
+                        };
+                        word.unwrap()
+                    })
+                })
+                .collect::<Vec<u64>>()
+        })
+    });
+
+    group.bench_function("bulk", |b| {
+        b.iter(|| {
+            let mut packed = vec![0u64; WORDS];
+            for (field, &(width, lsb, is_signed)) in FIELDS.iter().enumerate() {
+                if is_signed {
+                    pack_signed(&mut packed, width, lsb, black_box(&signed[field])).unwrap();
+                } else {
+                    pack_unsigned(&mut packed, width, lsb, black_box(&columns[field])).unwrap();
+                }
+            }
+            packed
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, unpack, pack);
+criterion_main!(benches);
@@ -0,0 +1,41 @@
+#![no_main]
+
+use bitpack::bitpack::{fitss, fitsu, gets, getu, news, newu};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (u64, u8, u8, u64, i64)| {
+    let (word, width, lsb, unsigned, signed) = input;
+    let (width, lsb) = (width as u64, lsb as u64);
+
+    // The bits covered by the field, if it lies within the word
+    let field = match width + lsb {
+        end if end > 64 => None,
+        _ if width == 0 => Some(0),
+        _ => Some(u64::MAX >> (64 - width) << lsb),
+    };
+
+    // Reading never panics, and only fails for fields outside the word
+    assert_eq!(getu(word, width, lsb).is_some(), field.is_some());
+    assert_eq!(gets(word, width, lsb).is_some(), field.is_some());
+
+    // Writing a value that fits stores it and leaves every other bit alone
+    match (newu(word, width, lsb, unsigned), field) {
+        (Some(new), Some(field)) => {
+            assert!(fitsu(unsigned, width));
+            assert_eq!(getu(new, width, lsb), Some(unsigned));
+            assert_eq!(new & !field, word & !field);
+        }
+        (None, field) => assert!(field.is_none() || !fitsu(unsigned, width)),
+        (Some(_), None) => panic!("newu wrote a field outside the word"),
+    }
+
+    match (news(word, width, lsb, signed), field) {
+        (Some(new), Some(field)) => {
+            assert!(fitss(signed, width));
+            assert_eq!(gets(new, width, lsb), Some(signed));
+            assert_eq!(new & !field, word & !field);
+        }
+        (None, field) => assert!(field.is_none() || !fitss(signed, width)),
+        (Some(_), None) => panic!("news wrote a field outside the word"),
+    }
+});
@@ -0,0 +1,30 @@
+#![no_main]
+
+use bitpack::huffman::{HuffmanCode, HuffmanDecoder};
+use bitpack::stream::{BitOrder, BitReader, BitWriter};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (bool, &[u8])| {
+    let (lsb_first, bytes) = input;
+    let order = if lsb_first { BitOrder::LsbFirst } else { BitOrder::MsbFirst };
+
+    // Damaged tables and codes are errors, never panics
+    let mut reader = BitReader::new(bytes, order);
+    let Ok(code) = HuffmanCode::read_table(&mut reader) else { return };
+    let decoder = HuffmanDecoder::new(&code);
+    let mut symbols = Vec::new();
+    while let Ok(symbol) = decoder.decode(&mut reader) {
+        symbols.push(symbol);
+    }
+
+    // Whatever decoded encodes back to the same symbols
+    let mut writer = BitWriter::new(Vec::new(), order);
+    for &symbol in &symbols {
+        code.encode(&mut writer, symbol).unwrap();
+    }
+    let encoded = writer.finish().unwrap();
+    let mut reader = BitReader::new(&encoded[..], order);
+    for &symbol in &symbols {
+        assert_eq!(decoder.decode(&mut reader).unwrap(), symbol);
+    }
+});
@@ -0,0 +1,52 @@
+#![no_main]
+
+use bitpack::range::{magnitude_context, BitModel, BitTree, IntModel, RangeDecoder, RangeEncoder};
+use libfuzzer_sys::fuzz_target;
+
+/// Number of integer contexts
+const CONTEXTS: usize = 4;
+
+fuzz_target!(|bytes: &[u8]| {
+    // Any bytes decode to some symbols until they run out, without panicking
+    let Ok(mut decoder) = RangeDecoder::new(bytes) else { return };
+    let (mut bit, mut tree, mut ints) = (BitModel::new(), BitTree::new(6), [IntModel::new(); CONTEXTS]);
+    let mut symbols = Vec::new();
+    let mut previous = 0;
+    loop {
+        let decoded = (|| {
+            let flag = decoder.decode_bit(&mut bit)?;
+            let small = decoder.decode_tree(&mut tree)?;
+            let int = decoder.decode_int(&mut ints[magnitude_context(&[previous], CONTEXTS)])?;
+            std::io::Result::Ok((flag, small, int))
+        })();
+        match decoded {
+            Ok(symbol) => {
+                previous = symbol.2;
+                symbols.push(symbol);
+            }
+            Err(_) => break,
+        }
+    }
+
+    // Whatever decoded encodes and decodes back to the same symbols
+    let (mut bit, mut tree, mut ints) = (BitModel::new(), BitTree::new(6), [IntModel::new(); CONTEXTS]);
+    let mut encoder = RangeEncoder::new(Vec::new());
+    let mut previous = 0;
+    for &(flag, small, int) in &symbols {
+        encoder.encode_bit(&mut bit, flag).unwrap();
+        encoder.encode_tree(&mut tree, small).unwrap();
+        encoder.encode_int(&mut ints[magnitude_context(&[previous], CONTEXTS)], int).unwrap();
+        previous = int;
+    }
+    let encoded = encoder.finish().unwrap();
+
+    let mut decoder = RangeDecoder::new(&encoded[..]).unwrap();
+    let (mut bit, mut tree, mut ints) = (BitModel::new(), BitTree::new(6), [IntModel::new(); CONTEXTS]);
+    let mut previous = 0;
+    for &(flag, small, int) in &symbols {
+        assert_eq!(decoder.decode_bit(&mut bit).unwrap(), flag);
+        assert_eq!(decoder.decode_tree(&mut tree).unwrap(), small);
+        assert_eq!(decoder.decode_int(&mut ints[magnitude_context(&[previous], CONTEXTS)]).unwrap(), int);
+        previous = int;
+    }
+});
@@ -0,0 +1,156 @@
+use crate::bitpack::BitpackError;
+use crate::word::Word;
+
+// Struct describing one field declared with `bitfield!`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldSpec {
+    pub name: &'static str,
+    pub width: u64,
+    pub lsb: u64,
+    pub signed: bool,
+}
+
+/// Panics unless every field is at most 64 bits wide, lies within a word of
+/// `word_bits` bits and overlaps no other field.
+/// `bitfield!` calls this in a constant, so a bad layout fails to compile.
+pub const fn check_fields(fields: &[FieldSpec], word_bits: u64) {
+    let mut i = 0;
+    while i < fields.len() {
+        let field = fields[i];
+        assert!(field.width <= 64, "a bitfield field is wider than 64 bits");
+        assert!(
+            field.width <= word_bits && field.lsb <= word_bits - field.width,
+            "a bitfield field runs past the end of the word"
+        );
+
+        let mut j = 0;
+        while j < i {
+            let other = fields[j];
+            let apart = field.width == 0
+                || other.width == 0
+                || field.lsb + field.width <= other.lsb
+                || other.lsb + other.width <= field.lsb;
+            assert!(apart, "bitfield fields overlap");
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+/// Writes an unsigned field declared with `bitfield!`
+pub fn pack_unsigned<W: Word>(word: W, width: u64, lsb: u64, value: u64) -> Result<W, BitpackError> {
+    let value = W::try_from(value).map_err(|_| BitpackError::ValueDoesNotFit { width })?;
+    word.new_unsigned(width, lsb, value)
+}
+
+/// Writes a signed field declared with `bitfield!`
+pub fn pack_signed<W: Word>(word: W, width: u64, lsb: u64, value: i64) -> Result<W, BitpackError> {
+    let value = W::Signed::try_from(value).map_err(|_| BitpackError::ValueDoesNotFit { width })?;
+    word.new_signed(width, lsb, value)
+}
+
+/// Reads an unsigned field declared with `bitfield!`, which `check_fields` has placed within the word
+pub fn unpack_unsigned<W: Word>(word: W, width: u64, lsb: u64) -> u64 {
+    word.get_unsigned(width, lsb)
+        .ok()
+        .and_then(|value| value.try_into().ok())
+        .expect("bitfield fields lie within the word")
+}
+
+/// Reads a signed field declared with `bitfield!`, which `check_fields` has placed within the word
+pub fn unpack_signed<W: Word>(word: W, width: u64, lsb: u64) -> i64 {
+    word.get_signed(width, lsb)
+        .ok()
+        .and_then(|value| value.try_into().ok())
+        .expect("bitfield fields lie within the word")
+}
+
+/// Declares a struct whose fields are packed into one word.
+///
+/// Each field is `unsigned(width, lsb)`, held as a `u64`, or `signed(width, lsb)`,
+/// held as an `i64`. The struct gets `FIELDS`, describing the fields in
+/// declaration order, `pack`, which fails if a value does not fit its field,
+/// and `unpack`. A field that overlaps another or runs past the end of the
+/// word is a compile error.
+///
+/// ```
+/// bitpack::bitfield! {
+///     #[derive(Debug, PartialEq)]
+///     pub struct Pixel: u16 {
+///         pub red: unsigned(5, 11),
+///         pub green: unsigned(6, 5),
+///         pub blue: unsigned(5, 0),
+///     }
+/// }
+///
+/// let pixel = Pixel { red: 31, green: 0, blue: 1 };
+/// assert_eq!(pixel.pack(), Ok(0xF801));
+/// assert_eq!(Pixel::unpack(0xF801), pixel);
+/// ```
+///
+/// ```compile_fail
+/// bitpack::bitfield! {
+///     pub struct Overlapping: u8 {
+///         pub low: unsigned(4, 0),
+///         pub high: signed(5, 3),
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! bitfield {
+    (@type unsigned) => { u64 };
+    (@type signed) => { i64 };
+    (@signed unsigned) => { false };
+    (@signed signed) => { true };
+    (@pack unsigned, $word:expr, $width:expr, $lsb:expr, $value:expr) => {
+        $crate::bitfield::pack_unsigned($word, $width, $lsb, $value)
+    };
+    (@pack signed, $word:expr, $width:expr, $lsb:expr, $value:expr) => {
+        $crate::bitfield::pack_signed($word, $width, $lsb, $value)
+    };
+    (@unpack unsigned, $word:expr, $width:expr, $lsb:expr) => {
+        $crate::bitfield::unpack_unsigned($word, $width, $lsb)
+    };
+    (@unpack signed, $word:expr, $width:expr, $lsb:expr) => {
+        $crate::bitfield::unpack_signed($word, $width, $lsb)
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident: $word:ty {
+            $($(#[$field_attr:meta])* $field_vis:vis $field:ident: $kind:ident($width:expr, $lsb:expr)),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $($(#[$field_attr])* $field_vis $field: $crate::bitfield!(@type $kind),)*
+        }
+
+        impl $name {
+            /// The fields in declaration order
+            pub const FIELDS: &'static [$crate::bitfield::FieldSpec] = &[$(
+                $crate::bitfield::FieldSpec {
+                    name: stringify!($field),
+                    width: $width,
+                    lsb: $lsb,
+                    signed: $crate::bitfield!(@signed $kind),
+                },
+            )*];
+
+            /// Packs the fields into a word, or reports the first value that does not fit its field
+            pub fn pack(&self) -> Result<$word, $crate::bitpack::BitpackError> {
+                let word = <$word as $crate::word::Word>::ZERO;
+                $(let word = $crate::bitfield!(@pack $kind, word, $width, $lsb, self.$field)?;)*
+                Ok(word)
+            }
+
+            /// Reads every field from `word`
+            pub fn unpack(word: $word) -> $name {
+                $name {
+                    $($field: $crate::bitfield!(@unpack $kind, word, $width, $lsb),)*
+                }
+            }
+        }
+
+        const _: () = $crate::bitfield::check_fields($name::FIELDS, <$word as $crate::word::Word>::BITS);
+    };
+}
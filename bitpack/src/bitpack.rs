@@ -1,5 +1,36 @@
-// use std::convert::TryInto;
+use std::fmt;
+
+use crate::word::Word;
+
+// Enum listing why a field could not be read or written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitpackError {
+    /// The field is wider than a word
+    WidthTooLarge { width: u64 },
+    /// The field runs past the most significant bit of the word
+    FieldOutOfWord { width: u64, lsb: u64 },
+    /// The value needs more bits than the field has
+    ValueDoesNotFit { width: u64 },
+}
+
+impl fmt::Display for BitpackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BitpackError::WidthTooLarge { width } => write!(f, "a field of {} bits is wider than a word", width),
+            BitpackError::FieldOutOfWord { width, lsb } => {
+                write!(f, "a field of {} bits at bit {} runs past the end of the word", width, lsb)
+            }
+            BitpackError::ValueDoesNotFit { width } => write!(f, "value does not fit in {} bits", width),
+        }
+    }
+}
+
+impl std::error::Error for BitpackError {}
 
+/// Checks that a field of `width` bits starting at `lsb` lies within a `u64`
+pub fn check_field(width: u64, lsb: u64) -> Result<(), BitpackError> {
+    u64::check_field(width, lsb)
+}
 
 /// Returns true iff the signed value `n` fits into `width` signed bits.
 /// 
@@ -7,9 +38,7 @@
 /// * `n`: A signed integer value
 /// * `width`: the width of a bit field
 pub fn fitss(n: i64, width: u64) -> bool {
-    // Check if the signed value fits within the specified width
-    n >= (-(1 << (width - 1))) && n <= ((1 << (width - 1)) - 1)
-
+    u64::fits_signed(n, width)
 }
 
 
@@ -20,45 +49,34 @@ pub fn fitss(n: i64, width: u64) -> bool {
 // /// * `n`: An usigned integer value
 // /// * `width`: the width of a bit field
 pub fn fitsu(n: u64, width: u64) -> bool {
-    // Check if the unsigned value fits within the specified width
-    n <= (1 << width) - 1
-
+    n.fits_unsigned(width)
 }
 
 // /// Retrieve a signed value from `word`, represented by `width` bits
 // /// beginning at least-significant bit `lsb`.
+// /// Returns None iff the field does not lie within the word,
+// /// see `get_signed` for the reason.
 // /// 
 // /// # Arguments:
 // /// * `word`: An unsigned word
 // /// * `width`: the width of a bit field
 // /// * `lsb`: the least-significant bit of the bit field
 pub fn gets(word: u64, width: u64, lsb: u64) -> Option<i64> {
-
-    let val = (word >> lsb) & ((1 << width) - 1);
-    
-    if (val & (1 << (width - 1))) != 0 {
-        // Perform sign extension if the value is negative
-        let sign_extension = !0 << width;
-        Some((val | sign_extension) as i64)
-    } else {
-        Some(val as i64)
-    }
-
+    get_signed(word, width, lsb).ok()
 }
 
 
 // /// Retrieve an unsigned value from `word`, represented by `width` bits
 // /// beginning at least-significant bit `lsb`.
+// /// Returns None iff the field does not lie within the word,
+// /// see `get_unsigned` for the reason.
 // /// 
 // /// # Arguments:
 // /// * `word`: An unsigned word
 // /// * `width`: the width of a bit field
 // /// * `lsb`: the least-significant bit of the bit field
 pub fn getu(word: u64, width: u64, lsb: u64) -> Option<u64> {
-
-    let val = (word >> lsb) & ((1 << width) - 1);
-    Some(val)
-
+    get_unsigned(word, width, lsb).ok()
 }
 
 
@@ -66,7 +84,8 @@ pub fn getu(word: u64, width: u64, lsb: u64) -> Option<u64> {
 // /// which has been updated so that the `width` bits beginning at
 // /// least-significant bit `lsb` now contain the unsigned `value`.
 // /// Returns an `Option` which will be None iff the value does not fit
-// /// in `width` unsigned bits.
+// /// in `width` unsigned bits or the field does not lie within the word,
+// /// see `new_unsigned` for the reason.
 // /// 
 // /// # Arguments:
 // /// * `word`: An unsigned word
@@ -74,16 +93,7 @@ pub fn getu(word: u64, width: u64, lsb: u64) -> Option<u64> {
 // /// * `lsb`: the least-significant bit of the bit field
 // /// * `value`: the unsigned value to place into that bit field
 pub fn newu(word: u64, width: u64, lsb: u64, value: u64) -> Option<u64> {
-    
-    if !fitsu(value, width) {
-        return None;
-    }
-
-    let clear_mask = !(((1 << width) - 1) << lsb);
-    let modified_word = (word & clear_mask) | (value << lsb);
-
-    Some(modified_word)
-   
+    new_unsigned(word, width, lsb, value).ok()
 }
 
 
@@ -92,7 +102,8 @@ pub fn newu(word: u64, width: u64, lsb: u64, value: u64) -> Option<u64> {
 // /// which has been updated so that the `width` bits beginning at
 // /// least-significant bit `lsb` now contain the signed `value`.
 // /// Returns an `Option` which will be None iff the value does not fit
-// /// in `width` signed bits.
+// /// in `width` signed bits or the field does not lie within the word,
+// /// see `new_signed` for the reason.
 // /// 
 // /// # Arguments:
 // /// * `word`: An unsigned word
@@ -100,17 +111,25 @@ pub fn newu(word: u64, width: u64, lsb: u64, value: u64) -> Option<u64> {
 // /// * `lsb`: the least-significant bit of the bit field
 // /// * `value`: the signed value to place into that bit field
 pub fn news(word: u64, width: u64, lsb: u64, value: i64) -> Option<u64> {
-    
-    if !fitss(value, width) {
-        return None;
-    }
-
-    let mask = (1_u64 << width) -1;
-    Some(word | ((value as u64 & mask) << lsb)) 
-    
+    new_signed(word, width, lsb, value).ok()
 }
 
+/// Reads the unsigned field of `width` bits at `lsb` from `word`
+pub fn get_unsigned(word: u64, width: u64, lsb: u64) -> Result<u64, BitpackError> {
+    word.get_unsigned(width, lsb)
+}
 
+/// Reads the signed field of `width` bits at `lsb` from `word`, sign extending it
+pub fn get_signed(word: u64, width: u64, lsb: u64) -> Result<i64, BitpackError> {
+    word.get_signed(width, lsb)
+}
 
+/// Returns `word` with the field of `width` bits at `lsb` replaced by the unsigned `value`
+pub fn new_unsigned(word: u64, width: u64, lsb: u64, value: u64) -> Result<u64, BitpackError> {
+    word.new_unsigned(width, lsb, value)
+}
 
-
+/// Returns `word` with the field of `width` bits at `lsb` replaced by the signed `value`
+pub fn new_signed(word: u64, width: u64, lsb: u64, value: i64) -> Result<u64, BitpackError> {
+    word.new_signed(width, lsb, value)
+}
@@ -0,0 +1,94 @@
+use crate::bitpack::BitpackError;
+use crate::word::Word;
+
+// Functions reading or writing one field of every word in a slice.
+// The field is checked once up front, so the loops are straight-line shifts
+// and masks with no branches or `Option`s, which the compiler can vectorise.
+
+/// Checks that a field lies within a word of `W` and fits in 64 bits
+fn check<W: Word>(width: u64, lsb: u64) -> Result<(), BitpackError> {
+    W::check_field(width, lsb)?;
+    if width > 64 {
+        return Err(BitpackError::WidthTooLarge { width });
+    }
+    Ok(())
+}
+
+/// A mask of the low `width` bits of a `u64`
+fn mask(width: u64) -> u64 {
+    u64::MAX.checked_shr((64 - width) as u32).unwrap_or(0)
+}
+
+/// Sign extends the low `width` bits of `bits`, for `width` from 1 to 64
+fn sign_extend(bits: u64, width: u64) -> i64 {
+    ((bits << (64 - width)) as i64) >> (64 - width)
+}
+
+/// Reads the unsigned field of `width` bits at `lsb` from every word
+pub fn unpack_unsigned<W: Word>(words: &[W], width: u64, lsb: u64) -> Result<Vec<u64>, BitpackError> {
+    check::<W>(width, lsb)?;
+    if width == 0 {
+        return Ok(vec![0; words.len()]);
+    }
+    let mask = mask(width);
+    Ok(words.iter().map(|&word| (word >> lsb).low_u64() & mask).collect())
+}
+
+/// Reads the signed field of `width` bits at `lsb` from every word, sign extending it
+pub fn unpack_signed<W: Word>(words: &[W], width: u64, lsb: u64) -> Result<Vec<i64>, BitpackError> {
+    check::<W>(width, lsb)?;
+    if width == 0 {
+        return Ok(vec![0; words.len()]);
+    }
+    Ok(words.iter().map(|&word| sign_extend((word >> lsb).low_u64(), width)).collect())
+}
+
+/// Replaces the unsigned field of `width` bits at `lsb` in each word with the
+/// matching value. Nothing is written unless every value fits.
+/// Panics if the slices differ in length.
+pub fn pack_unsigned<W: Word>(words: &mut [W], width: u64, lsb: u64, values: &[u64]) -> Result<(), BitpackError> {
+    assert_eq!(words.len(), values.len(), "every word needs a value");
+    check::<W>(width, lsb)?;
+
+    // Or every value together, so one test covers them all
+    let mask = mask(width);
+    if values.iter().fold(0, |bits, &value| bits | value) & !mask != 0 {
+        return Err(BitpackError::ValueDoesNotFit { width });
+    }
+    if width == 0 {
+        return Ok(());
+    }
+
+    let clear = !(W::low_mask(width) << lsb);
+    for (word, &value) in words.iter_mut().zip(values) {
+        *word = (*word & clear) | (W::from_u64(value) << lsb);
+    }
+    Ok(())
+}
+
+/// Replaces the signed field of `width` bits at `lsb` in each word with the
+/// matching value. Nothing is written unless every value fits.
+/// Panics if the slices differ in length.
+pub fn pack_signed<W: Word>(words: &mut [W], width: u64, lsb: u64, values: &[i64]) -> Result<(), BitpackError> {
+    assert_eq!(words.len(), values.len(), "every word needs a value");
+    check::<W>(width, lsb)?;
+    if width == 0 {
+        return match values.iter().all(|&value| value == 0) {
+            true => Ok(()),
+            false => Err(BitpackError::ValueDoesNotFit { width }),
+        };
+    }
+
+    // A value fits iff sign extending its low bits gives it back
+    let mask = mask(width);
+    let changed = values.iter().fold(0, |bits, &value| bits | (value ^ sign_extend(value as u64 & mask, width)));
+    if changed != 0 {
+        return Err(BitpackError::ValueDoesNotFit { width });
+    }
+
+    let clear = !(W::low_mask(width) << lsb);
+    for (word, &value) in words.iter_mut().zip(values) {
+        *word = (*word & clear) | (W::from_u64(value as u64 & mask) << lsb);
+    }
+    Ok(())
+}
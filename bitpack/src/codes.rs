@@ -0,0 +1,214 @@
+use std::io::{self, Read, Write};
+
+use crate::bitpack;
+use crate::stream::{invalid, BitReader, BitWriter};
+
+/// Number of payload bits in each LEB128 byte
+const LEB128_GROUP: u64 = 7;
+
+/// Most bytes a 64-bit LEB128 value can take
+const LEB128_MAX_BYTES: u64 = 10;
+
+/// Maps a signed value to an unsigned one so small magnitudes get small codes:
+/// 0, -1, 1, -2, 2 become 0, 1, 2, 3, 4
+pub fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+/// Undoes `zigzag`
+pub fn unzigzag(n: u64) -> i64 {
+    (n >> 1) as i64 ^ -((n & 1) as i64)
+}
+
+/// The error for a value a code cannot represent
+fn unrepresentable(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// The error for a code that decodes to more than 64 bits
+fn too_long(code: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{} code does not fit in 64 bits", code))
+}
+
+impl<W: Write> BitWriter<W> {
+    /// Writes `count` zero bits followed by a one bit
+    fn write_unary(&mut self, count: u64) -> io::Result<()> {
+        let mut left = count;
+        while left > 0 {
+            let n = left.min(64);
+            self.writeu(n, 0)?;
+            left -= n;
+        }
+        self.writeu(1, 1)
+    }
+
+    /// Writes `value` as an Exp-Golomb code of order `k`: the number of
+    /// bits in `value + 2^k` beyond `k + 1` in unary, then those bits.
+    /// Fails if `k` is over 64.
+    pub fn write_exp_golomb(&mut self, k: u64, value: u64) -> io::Result<()> {
+        bitpack::check_field(k, 0).map_err(invalid)?;
+
+        // The offset value can need 65 bits, but the one bit ending the unary
+        // prefix is its leading bit, so at most 64 bits follow it
+        let offset = value as u128 + (1u128 << k);
+        let bits = 128 - offset.leading_zeros() as u64;
+        self.write_unary(bits - 1 - k)?;
+        self.writeu(bits - 1, (offset - (1u128 << (bits - 1))) as u64)
+    }
+
+    /// Writes `value` as a Golomb-Rice code with parameter `k`: `value >> k`
+    /// in unary, then the low `k` bits. Fails if `k` is over 64.
+    /// The unary part grows with `value >> k`, so pick `k` near the bit
+    /// length of typical values.
+    pub fn write_rice(&mut self, k: u64, value: u64) -> io::Result<()> {
+        bitpack::check_field(k, 0).map_err(invalid)?;
+        let quotient = value.checked_shr(k as u32).unwrap_or(0);
+        self.write_unary(quotient)?;
+        self.writeu(k, bitpack::getu(value, k, 0).unwrap())
+    }
+
+    /// Writes `value` as an Elias gamma code: one less than its number of
+    /// bits in unary, then its bits. Fails if `value` is 0.
+    pub fn write_gamma(&mut self, value: u64) -> io::Result<()> {
+        if value == 0 {
+            return Err(unrepresentable("Elias codes start at 1"));
+        }
+        let bits = 64 - value.leading_zeros() as u64;
+        self.write_unary(bits - 1)?;
+        self.writeu(bits - 1, bitpack::getu(value, bits - 1, 0).unwrap())
+    }
+
+    /// Writes `value` as an Elias delta code: its number of bits as a gamma
+    /// code, then its bits below the leading one. Fails if `value` is 0.
+    pub fn write_delta(&mut self, value: u64) -> io::Result<()> {
+        if value == 0 {
+            return Err(unrepresentable("Elias codes start at 1"));
+        }
+        let bits = 64 - value.leading_zeros() as u64;
+        self.write_gamma(bits)?;
+        self.writeu(bits - 1, bitpack::getu(value, bits - 1, 0).unwrap())
+    }
+
+    /// Writes `value` as an unsigned LEB128 varint: 7 bits per byte, least
+    /// significant group first, with the top bit set on every byte but the last
+    pub fn write_leb128(&mut self, value: u64) -> io::Result<()> {
+        let mut rest = value;
+        loop {
+            let group = rest & 0x7F;
+            rest >>= LEB128_GROUP;
+            if rest == 0 {
+                return self.writeu(8, group);
+            }
+            self.writeu(8, group | 0x80)?;
+        }
+    }
+
+    /// Writes `value` as a signed LEB128 varint, which ends once the rest of
+    /// the value is the sign extension of the last group
+    pub fn write_sleb128(&mut self, value: i64) -> io::Result<()> {
+        let mut rest = value;
+        loop {
+            let group = (rest & 0x7F) as u64;
+            rest >>= LEB128_GROUP;
+            let sign_bit = group & 0x40 != 0;
+            if (rest == 0 && !sign_bit) || (rest == -1 && sign_bit) {
+                return self.writeu(8, group);
+            }
+            self.writeu(8, group | 0x80)?;
+        }
+    }
+}
+
+impl<R: Read> BitReader<R> {
+    /// Counts zero bits up to the next one bit, failing once there are more than `limit`
+    fn read_unary(&mut self, limit: u64, code: &str) -> io::Result<u64> {
+        let mut count = 0;
+        while self.readu(1)? == 0 {
+            if count == limit {
+                return Err(too_long(code));
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Reads an Exp-Golomb code of order `k`.
+    /// Fails if `k` is over 64 or the code does not fit in 64 bits.
+    pub fn read_exp_golomb(&mut self, k: u64) -> io::Result<u64> {
+        bitpack::check_field(k, 0).map_err(invalid)?;
+
+        // The offset value has at most 65 bits, the first of which ends the unary prefix
+        let bits = self.read_unary(64 - k, "Exp-Golomb")? + 1 + k;
+        let offset = 1u128 << (bits - 1) | self.readu(bits - 1)? as u128;
+        u64::try_from(offset - (1u128 << k)).map_err(|_| too_long("Exp-Golomb"))
+    }
+
+    /// Reads a Golomb-Rice code with parameter `k`.
+    /// Fails if `k` is over 64 or the code does not fit in 64 bits.
+    pub fn read_rice(&mut self, k: u64) -> io::Result<u64> {
+        bitpack::check_field(k, 0).map_err(invalid)?;
+        let limit = u64::MAX.checked_shr(k as u32).unwrap_or(0);
+        let quotient = self.read_unary(limit, "Rice")?;
+        let remainder = self.readu(k)?;
+        Ok(quotient.checked_shl(k as u32).unwrap_or(0) | remainder)
+    }
+
+    /// Reads an Elias gamma code. Fails if the code does not fit in 64 bits.
+    pub fn read_gamma(&mut self) -> io::Result<u64> {
+        let bits = self.read_unary(63, "Elias gamma")? + 1;
+        Ok(1u64 << (bits - 1) | self.readu(bits - 1)?)
+    }
+
+    /// Reads an Elias delta code. Fails if the code does not fit in 64 bits.
+    pub fn read_delta(&mut self) -> io::Result<u64> {
+        let bits = self.read_gamma()?;
+        if bits > 64 {
+            return Err(too_long("Elias delta"));
+        }
+        Ok(1u64 << (bits - 1) | self.readu(bits - 1)?)
+    }
+
+    /// Reads an unsigned LEB128 varint. Fails if it does not fit in 64 bits.
+    pub fn read_leb128(&mut self) -> io::Result<u64> {
+        let mut value = 0u64;
+        for index in 0..LEB128_MAX_BYTES {
+            let byte = self.readu(8)?;
+            let shift = index * LEB128_GROUP;
+            let group = byte & 0x7F;
+            if group.checked_shl(shift as u32).unwrap_or(0) >> shift != group {
+                return Err(too_long("LEB128"));
+            }
+            value |= group << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(too_long("LEB128"))
+    }
+
+    /// Reads a signed LEB128 varint. Fails if it does not fit in 64 bits.
+    pub fn read_sleb128(&mut self) -> io::Result<i64> {
+        let mut value = 0i64;
+        for index in 0..LEB128_MAX_BYTES {
+            let byte = self.readu(8)?;
+            let shift = index * LEB128_GROUP;
+            let group = (byte & 0x7F) as i64;
+
+            // The tenth byte holds the top bit, and the bits above it must repeat it
+            if index == LEB128_MAX_BYTES - 1 && group != 0 && group != 0x7F {
+                return Err(too_long("signed LEB128"));
+            }
+            value |= group << shift;
+
+            if byte & 0x80 == 0 {
+                // Sign extend from the top bit of the last group
+                let used = shift + LEB128_GROUP;
+                if used < 64 && group & 0x40 != 0 {
+                    value |= -1i64 << used;
+                }
+                return Ok(value);
+            }
+        }
+        Err(too_long("signed LEB128"))
+    }
+}
@@ -0,0 +1,157 @@
+use crate::bitpack::{self, BitpackError};
+
+// Enum listing how a value between two fixed-point steps is rounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// To the nearest step, with ties away from zero
+    #[default]
+    Nearest,
+    /// To the nearest step, with ties to the even one
+    NearestEven,
+    /// Toward negative infinity
+    Floor,
+    /// Toward positive infinity
+    Ceil,
+    /// Toward zero
+    TowardZero,
+}
+
+// Enum listing what happens to a value outside a format's range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Clamp it to the nearest value the format holds
+    #[default]
+    Saturate,
+    /// Report that it does not fit
+    Fail,
+}
+
+// Struct describing a binary fixed-point number of `int_bits` integer bits
+// and `frac_bits` fraction bits. The integer bits of a signed format include
+// the sign bit, so a signed format with 1 integer bit covers [-1, 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedFormat {
+    int_bits: u64,
+    frac_bits: u64,
+    signed: bool,
+}
+
+impl FixedFormat {
+    /// An unsigned format. Panics, at compile time in a constant, if it is over 64 bits.
+    pub const fn unsigned(int_bits: u64, frac_bits: u64) -> FixedFormat {
+        assert!(int_bits + frac_bits <= 64, "fixed-point formats are at most 64 bits wide");
+        FixedFormat { int_bits, frac_bits, signed: false }
+    }
+
+    /// A signed two's complement format. Panics, at compile time in a
+    /// constant, if it is over 64 bits or has no sign bit.
+    pub const fn signed(int_bits: u64, frac_bits: u64) -> FixedFormat {
+        assert!(int_bits + frac_bits <= 64, "fixed-point formats are at most 64 bits wide");
+        assert!(int_bits + frac_bits > 0, "signed fixed-point formats need a sign bit");
+        FixedFormat { int_bits, frac_bits, signed: true }
+    }
+
+    /// Number of integer bits, including the sign bit of a signed format
+    pub fn int_bits(&self) -> u64 {
+        self.int_bits
+    }
+
+    /// Number of fraction bits
+    pub fn frac_bits(&self) -> u64 {
+        self.frac_bits
+    }
+
+    /// True for a signed format
+    pub fn is_signed(&self) -> bool {
+        self.signed
+    }
+
+    /// Number of bits in a field of this format
+    pub fn width(&self) -> u64 {
+        self.int_bits + self.frac_bits
+    }
+
+    /// The smallest and largest number of steps the format holds
+    fn step_range(&self) -> (i128, i128) {
+        let width = self.width() as u32;
+        match (self.signed, width) {
+            (_, 0) => (0, 0),
+            (true, _) => (-(1 << (width - 1)), (1 << (width - 1)) - 1),
+            (false, _) => (0, (1 << width) - 1),
+        }
+    }
+
+    /// The difference between neighbouring values
+    pub fn step(&self) -> f64 {
+        (-(self.frac_bits as f64)).exp2()
+    }
+
+    /// The smallest value the format holds
+    pub fn min(&self) -> f64 {
+        self.step_range().0 as f64 * self.step()
+    }
+
+    /// The largest value the format holds
+    pub fn max(&self) -> f64 {
+        self.step_range().1 as f64 * self.step()
+    }
+
+    /// The field bits of `value`, rounded to a step by `rounding`.
+    /// Values outside the range saturate or fail as `overflow` says, and NaN always fails.
+    pub fn to_bits(&self, value: f64, rounding: Rounding, overflow: Overflow) -> Result<u64, BitpackError> {
+        let width = self.width();
+        if value.is_nan() {
+            return Err(BitpackError::ValueDoesNotFit { width });
+        }
+
+        // Scaling by a power of two is exact, so only the rounding loses precision
+        let scaled = value * (self.frac_bits as f64).exp2();
+        let rounded = match rounding {
+            Rounding::Nearest => scaled.round(),
+            Rounding::NearestEven => scaled.round_ties_even(),
+            Rounding::Floor => scaled.floor(),
+            Rounding::Ceil => scaled.ceil(),
+            Rounding::TowardZero => scaled.trunc(),
+        };
+
+        // Infinities and values past i128 saturate in the cast, and are then clamped
+        let (min, max) = self.step_range();
+        let steps = rounded as i128;
+        if (steps < min || steps > max) && overflow == Overflow::Fail {
+            return Err(BitpackError::ValueDoesNotFit { width });
+        }
+        let steps = steps.clamp(min, max);
+
+        // Keep the two's complement bits of the field
+        Ok(steps as u64 & u64::MAX.checked_shr(64 - width as u32).unwrap_or(0))
+    }
+
+    /// The value of the field bits written by `to_bits`.
+    /// Exact for formats of up to 53 bits, the precision of an `f64`.
+    pub fn from_bits(&self, bits: u64) -> f64 {
+        let width = self.width();
+        let steps = if self.signed {
+            bitpack::gets(bits, width, 0).unwrap_or(0) as f64
+        } else {
+            bitpack::getu(bits, width, 0).unwrap_or(0) as f64
+        };
+        steps * self.step()
+    }
+
+    /// Returns `word` with the field at `lsb` replaced by `value`, converted by `to_bits`
+    pub fn new_field(
+        &self,
+        word: u64,
+        lsb: u64,
+        value: f64,
+        rounding: Rounding,
+        overflow: Overflow,
+    ) -> Result<u64, BitpackError> {
+        bitpack::new_unsigned(word, self.width(), lsb, self.to_bits(value, rounding, overflow)?)
+    }
+
+    /// Reads the field at `lsb` from `word` as a value of this format
+    pub fn get_field(&self, word: u64, lsb: u64) -> Result<f64, BitpackError> {
+        Ok(self.from_bits(bitpack::get_unsigned(word, self.width(), lsb)?))
+    }
+}
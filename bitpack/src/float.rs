@@ -0,0 +1,155 @@
+use crate::bitpack::{self, BitpackError};
+
+/// Bits in the mantissa of an `f64`, not counting the implicit leading one
+const F64_MANTISSA_BITS: u64 = 52;
+
+/// Exponent of the smallest subnormal `f64`
+const F64_MIN_EXP: i64 = -1074;
+
+// Struct describing a reduced-precision binary float laid out like IEEE 754:
+// a sign bit, then `exponent_bits` of biased exponent, then `mantissa_bits`
+// of mantissa. Formats have subnormals, infinities and NaN, and every value
+// of a format is an `f64`, so converting back is exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FloatFormat {
+    exponent_bits: u64,
+    mantissa_bits: u64,
+}
+
+/// An `f64` of `2^exp`, for `exp` within the range of `f64` including subnormals
+fn pow2(exp: i64) -> f64 {
+    if exp >= -1022 {
+        f64::from_bits(((exp + 1023) as u64) << F64_MANTISSA_BITS)
+    } else {
+        f64::from_bits(1 << (exp - F64_MIN_EXP))
+    }
+}
+
+/// `n / 2^shift` rounded to the nearest integer, with ties to even
+fn shift_round_even(n: u128, shift: i64) -> u128 {
+    if shift <= 0 {
+        return n << -shift;
+    }
+    if shift > 127 {
+        return 0;
+    }
+    let quotient = n >> shift;
+    let remainder = n & ((1 << shift) - 1);
+    let half = 1 << (shift - 1);
+    if remainder > half || (remainder == half && quotient & 1 == 1) { quotient + 1 } else { quotient }
+}
+
+impl FloatFormat {
+    /// IEEE 754 half precision: 5 exponent and 10 mantissa bits
+    pub const HALF: FloatFormat = FloatFormat::new(5, 10);
+
+    /// bfloat16: the exponent range of an `f32` with 7 mantissa bits
+    pub const BFLOAT16: FloatFormat = FloatFormat::new(8, 7);
+
+    /// IEEE 754 single precision, the layout of an `f32`
+    pub const SINGLE: FloatFormat = FloatFormat::new(8, 23);
+
+    /// A format with the given field widths. Panics, at compile time in a
+    /// constant, unless the exponent has 2 to 11 bits and the mantissa 1 to
+    /// 52, so that every value is an `f64` and NaN has a code.
+    pub const fn new(exponent_bits: u64, mantissa_bits: u64) -> FloatFormat {
+        assert!(exponent_bits >= 2 && exponent_bits <= 11, "float exponents have 2 to 11 bits");
+        assert!(mantissa_bits >= 1 && mantissa_bits <= 52, "float mantissas have 1 to 52 bits");
+        FloatFormat { exponent_bits, mantissa_bits }
+    }
+
+    /// Number of exponent bits
+    pub fn exponent_bits(&self) -> u64 {
+        self.exponent_bits
+    }
+
+    /// Number of mantissa bits, not counting the implicit leading one
+    pub fn mantissa_bits(&self) -> u64 {
+        self.mantissa_bits
+    }
+
+    /// Number of bits in a field of this format
+    pub fn width(&self) -> u64 {
+        1 + self.exponent_bits + self.mantissa_bits
+    }
+
+    /// The exponent bias
+    fn bias(&self) -> i64 {
+        (1 << (self.exponent_bits - 1)) - 1
+    }
+
+    /// The biased exponent of infinities and NaN
+    fn max_exponent(&self) -> u64 {
+        (1 << self.exponent_bits) - 1
+    }
+
+    /// The field bits of `value`, rounded to the nearest value of the format
+    /// with ties to even. Values too large for the format become infinite,
+    /// and every NaN becomes the same quiet NaN.
+    pub fn to_bits(&self, value: f64) -> u64 {
+        let m = self.mantissa_bits;
+        let sign = (value.is_sign_negative() as u64) << (self.exponent_bits + m);
+        let infinity = self.max_exponent() << m;
+
+        if value.is_nan() {
+            return infinity | 1 << (m - 1);
+        }
+        let magnitude = value.abs();
+        if magnitude.is_infinite() {
+            return sign | infinity;
+        }
+        if magnitude == 0.0 {
+            return sign;
+        }
+
+        // magnitude = mantissa * 2^exp exactly
+        let bits = magnitude.to_bits();
+        let biased = (bits >> F64_MANTISSA_BITS) as i64;
+        let fraction = bits & ((1 << F64_MANTISSA_BITS) - 1);
+        let (mantissa, exp) = match biased {
+            0 => (fraction, F64_MIN_EXP),
+            _ => (fraction | 1 << F64_MANTISSA_BITS, biased - 1075),
+        };
+
+        // Values below the smallest normal are subnormals that share its exponent
+        let top = exp + 63 - mantissa.leading_zeros() as i64;
+        let min_exp = 1 - self.bias();
+        let exponent = top.max(min_exp);
+        let steps = shift_round_even(mantissa as u128, exponent - m as i64 - exp);
+
+        // Rounding up to the next power of two carries into the exponent by itself
+        let code = ((exponent + self.bias() - 1) as u128) << m;
+        let code = code + steps;
+        if code >= infinity as u128 {
+            return sign | infinity;
+        }
+        sign | code as u64
+    }
+
+    /// The value of the field bits written by `to_bits`
+    pub fn from_bits(&self, bits: u64) -> f64 {
+        let m = self.mantissa_bits;
+        let mantissa = bitpack::getu(bits, m, 0).unwrap();
+        let exponent = bitpack::getu(bits, self.exponent_bits, m).unwrap();
+        let negative = bitpack::getu(bits, 1, m + self.exponent_bits).unwrap() == 1;
+
+        let magnitude = if exponent == self.max_exponent() {
+            if mantissa == 0 { f64::INFINITY } else { f64::NAN }
+        } else if exponent == 0 {
+            mantissa as f64 * pow2(1 - self.bias() - m as i64)
+        } else {
+            (mantissa | 1 << m) as f64 * pow2(exponent as i64 - self.bias() - m as i64)
+        };
+        if negative { -magnitude } else { magnitude }
+    }
+
+    /// Returns `word` with the field at `lsb` replaced by `value`, converted by `to_bits`
+    pub fn new_field(&self, word: u64, lsb: u64, value: f64) -> Result<u64, BitpackError> {
+        bitpack::new_unsigned(word, self.width(), lsb, self.to_bits(value))
+    }
+
+    /// Reads the field at `lsb` from `word` as a value of this format
+    pub fn get_field(&self, word: u64, lsb: u64) -> Result<f64, BitpackError> {
+        Ok(self.from_bits(bitpack::get_unsigned(word, self.width(), lsb)?))
+    }
+}
@@ -0,0 +1,315 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::codes::{unzigzag, zigzag};
+use crate::stream::{BitOrder, BitReader, BitWriter};
+
+/// Longest code a table may hold
+pub const MAX_CODE_LEN: u64 = 32;
+
+/// Largest alphabet a table read from a stream may describe
+pub const MAX_SYMBOLS: usize = 1 << 20;
+
+/// Most code bits the decoder resolves with a single table lookup
+const LOOKUP_BITS: u64 = 10;
+
+// Enum listing why a Huffman code could not be built
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HuffmanError {
+    /// More symbols are used than codes of at most `max_len` bits can tell apart
+    TooManySymbols { symbols: usize, max_len: u64 },
+    /// A code is longer than `MAX_CODE_LEN`
+    CodeTooLong { len: u64 },
+    /// The code lengths describe more codes than there are bit patterns
+    Oversubscribed,
+    /// The symbol has no code
+    UnknownSymbol { symbol: usize },
+}
+
+impl fmt::Display for HuffmanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HuffmanError::TooManySymbols { symbols, max_len } => {
+                write!(f, "{} symbols do not fit in codes of at most {} bits", symbols, max_len)
+            }
+            HuffmanError::CodeTooLong { len } => {
+                write!(f, "a code of {} bits is longer than the limit of {}", len, MAX_CODE_LEN)
+            }
+            HuffmanError::Oversubscribed => write!(f, "the code lengths describe more codes than fit"),
+            HuffmanError::UnknownSymbol { symbol } => write!(f, "symbol {} has no code", symbol),
+        }
+    }
+}
+
+impl std::error::Error for HuffmanError {}
+
+/// The error for a code or table that cannot be written
+fn unwritable(error: HuffmanError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, error)
+}
+
+/// The error for bits that are not a valid code or table
+fn corrupt(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// The low `len` bits of `code` in reverse order
+fn reverse(code: u64, len: u64) -> u64 {
+    code.reverse_bits().checked_shr(64 - len as u32).unwrap_or(0)
+}
+
+/// Merges two lists sorted by weight, taking from `a` first on ties
+fn merge(a: Vec<(u128, Vec<usize>)>, b: Vec<(u128, Vec<usize>)>) -> Vec<(u128, Vec<usize>)> {
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    let (mut a, mut b) = (a.into_iter().peekable(), b.into_iter().peekable());
+    while let (Some(x), Some(y)) = (a.peek(), b.peek()) {
+        let next = if x.0 <= y.0 { a.next() } else { b.next() };
+        merged.extend(next);
+    }
+    merged.extend(a);
+    merged.extend(b);
+    merged
+}
+
+/// Optimal code lengths of at most `max_len` bits, found by package-merge.
+/// Every level pairs the cheapest items of the level below into packages and
+/// merges them with the symbols. The cheapest `2(n - 1)` items of the last
+/// level then hold each symbol once for every bit of its code.
+fn limited_lengths(frequencies: &[u64], max_len: u64) -> Result<Vec<u8>, HuffmanError> {
+    let mut leaves: Vec<(u128, Vec<usize>)> = frequencies
+        .iter()
+        .enumerate()
+        .filter(|&(_, &frequency)| frequency > 0)
+        .map(|(symbol, &frequency)| (frequency as u128, vec![symbol]))
+        .collect();
+    leaves.sort();
+
+    let mut lengths = vec![0u8; frequencies.len()];
+    let n = leaves.len();
+    if n == 0 {
+        return Ok(lengths);
+    }
+    if max_len == 0 || n as u128 > 1 << max_len {
+        return Err(HuffmanError::TooManySymbols { symbols: n, max_len });
+    }
+
+    // A lone symbol still needs one bit so it can be written
+    if n == 1 {
+        lengths[leaves[0].1[0]] = 1;
+        return Ok(lengths);
+    }
+
+    let mut items = leaves.clone();
+    for _ in 1..max_len {
+        let packages = items
+            .chunks_exact(2)
+            .map(|pair| (pair[0].0 + pair[1].0, [&pair[0].1[..], &pair[1].1[..]].concat()))
+            .collect();
+        items = merge(leaves.clone(), packages);
+    }
+    for (_, symbols) in &items[..2 * (n - 1)] {
+        for &symbol in symbols {
+            lengths[symbol] += 1;
+        }
+    }
+    Ok(lengths)
+}
+
+/// Symbols with a code, shortest codes first and then in symbol order
+fn canonical_order(lengths: &[u8]) -> Vec<usize> {
+    let mut sorted: Vec<usize> = (0..lengths.len()).filter(|&symbol| lengths[symbol] > 0).collect();
+    sorted.sort_by_key(|&symbol| (lengths[symbol], symbol));
+    sorted
+}
+
+// Struct holding a canonical prefix code. Codes of the same length are
+// consecutive in symbol order and shorter codes come first, so the lengths
+// alone describe the whole code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HuffmanCode {
+    lengths: Vec<u8>,
+    codes: Vec<u64>,
+}
+
+impl HuffmanCode {
+    /// Builds the code of least total length for symbols `0..frequencies.len()`,
+    /// with no code longer than `max_len` bits. Symbols of frequency 0 get no code.
+    /// Fails if `max_len` is over `MAX_CODE_LEN` or too short for the symbols used.
+    pub fn from_frequencies(frequencies: &[u64], max_len: u64) -> Result<HuffmanCode, HuffmanError> {
+        if max_len > MAX_CODE_LEN {
+            return Err(HuffmanError::CodeTooLong { len: max_len });
+        }
+        HuffmanCode::from_lengths(&limited_lengths(frequencies, max_len)?)
+    }
+
+    /// Builds the canonical code with the given length for each symbol, 0 for none.
+    /// The code need not be complete, but fails if it is oversubscribed or
+    /// a length is over `MAX_CODE_LEN`.
+    pub fn from_lengths(lengths: &[u8]) -> Result<HuffmanCode, HuffmanError> {
+        if let Some(&len) = lengths.iter().find(|&&len| len as u64 > MAX_CODE_LEN) {
+            return Err(HuffmanError::CodeTooLong { len: len as u64 });
+        }
+
+        // Kraft's inequality, in units of the shortest possible code
+        let used: u64 = lengths.iter().filter(|&&len| len > 0).map(|&len| 1 << (MAX_CODE_LEN - len as u64)).sum();
+        if used > 1 << MAX_CODE_LEN {
+            return Err(HuffmanError::Oversubscribed);
+        }
+
+        let mut codes = vec![0; lengths.len()];
+        let mut next = 0u64;
+        let mut previous = 0;
+        for symbol in canonical_order(lengths) {
+            next <<= lengths[symbol] - previous;
+            codes[symbol] = next;
+            next += 1;
+            previous = lengths[symbol];
+        }
+        Ok(HuffmanCode { lengths: lengths.to_vec(), codes })
+    }
+
+    /// The code length of each symbol, 0 for symbols with no code
+    pub fn lengths(&self) -> &[u8] {
+        &self.lengths
+    }
+
+    /// The length and bits of the code for `symbol`, if it has one
+    pub fn code(&self, symbol: usize) -> Option<(u64, u64)> {
+        match self.lengths.get(symbol) {
+            Some(&len) if len > 0 => Some((len as u64, self.codes[symbol])),
+            _ => None,
+        }
+    }
+
+    /// The length of the longest code
+    pub fn max_len(&self) -> u64 {
+        self.lengths.iter().copied().max().unwrap_or(0) as u64
+    }
+
+    /// Writes the code for `symbol`, first bit of the code first in either bit order.
+    /// Fails if the symbol has no code.
+    pub fn encode<W: Write>(&self, writer: &mut BitWriter<W>, symbol: usize) -> io::Result<()> {
+        let (len, code) = self.code(symbol).ok_or(unwritable(HuffmanError::UnknownSymbol { symbol }))?;
+        match writer.order() {
+            BitOrder::MsbFirst => writer.writeu(len, code),
+            BitOrder::LsbFirst => writer.writeu(len, reverse(code, len)),
+        }
+    }
+
+    /// Writes the code table: the number of symbols, then runs of equal
+    /// lengths as the zigzagged change from the previous run and the run
+    /// length less one, all as Exp-Golomb codes of order 0
+    pub fn write_table<W: Write>(&self, writer: &mut BitWriter<W>) -> io::Result<()> {
+        writer.write_exp_golomb(0, self.lengths.len() as u64)?;
+        let mut previous = 0;
+        for run in self.lengths.chunk_by(|a, b| a == b) {
+            writer.write_exp_golomb(0, zigzag(run[0] as i64 - previous))?;
+            writer.write_exp_golomb(0, run.len() as u64 - 1)?;
+            previous = run[0] as i64;
+        }
+        Ok(())
+    }
+
+    /// Reads a code table written by `write_table`.
+    /// Fails if it describes more than `MAX_SYMBOLS` symbols or an invalid code.
+    pub fn read_table<R: Read>(reader: &mut BitReader<R>) -> io::Result<HuffmanCode> {
+        let symbols = reader.read_exp_golomb(0)?;
+        if symbols > MAX_SYMBOLS as u64 {
+            return Err(corrupt(format!("a table of {} symbols is over the limit of {}", symbols, MAX_SYMBOLS)));
+        }
+
+        let mut lengths = Vec::with_capacity(symbols as usize);
+        let mut previous = 0i64;
+        while lengths.len() < symbols as usize {
+            let len = previous.checked_add(unzigzag(reader.read_exp_golomb(0)?));
+            let len = match len {
+                Some(len) if (0..=MAX_CODE_LEN as i64).contains(&len) => len,
+                _ => return Err(corrupt("code length out of range")),
+            };
+            let run = reader.read_exp_golomb(0)?.saturating_add(1);
+            if run > (symbols as usize - lengths.len()) as u64 {
+                return Err(corrupt("run of code lengths past the last symbol"));
+            }
+            lengths.resize(lengths.len() + run as usize, len as u8);
+            previous = len;
+        }
+        HuffmanCode::from_lengths(&lengths).map_err(corrupt)
+    }
+}
+
+// Struct decoding symbols of a `HuffmanCode`. Codes of up to `lookup_bits`
+// bits are found with one lookup of the next `lookup_bits` bits in `table`.
+// Longer codes are found a length at a time from the first code and number
+// of codes of each length, indexing `sorted`.
+#[derive(Debug, Clone)]
+pub struct HuffmanDecoder {
+    lookup_bits: u64,
+    table: Vec<Option<(usize, u64)>>,
+    max_len: u64,
+    first: Vec<u64>,
+    count: Vec<u64>,
+    offset: Vec<usize>,
+    sorted: Vec<usize>,
+}
+
+impl HuffmanDecoder {
+    /// Builds the decoding tables of `code`
+    pub fn new(code: &HuffmanCode) -> HuffmanDecoder {
+        let max_len = code.max_len();
+        let lookup_bits = max_len.min(LOOKUP_BITS);
+        let sorted = canonical_order(&code.lengths);
+
+        // Every pattern that starts with a short code decodes to its symbol
+        let mut table = vec![None; 1 << lookup_bits];
+        for &symbol in &sorted {
+            let (len, bits) = (code.lengths[symbol] as u64, code.codes[symbol]);
+            if len <= lookup_bits {
+                let start = (bits << (lookup_bits - len)) as usize;
+                table[start..start + (1 << (lookup_bits - len))].fill(Some((symbol, len)));
+            }
+        }
+
+        let mut first = vec![0; max_len as usize + 1];
+        let mut count = vec![0; max_len as usize + 1];
+        let mut offset = vec![0; max_len as usize + 1];
+        for (index, &symbol) in sorted.iter().enumerate() {
+            let len = code.lengths[symbol] as usize;
+            if count[len] == 0 {
+                first[len] = code.codes[symbol];
+                offset[len] = index;
+            }
+            count[len] += 1;
+        }
+
+        HuffmanDecoder { lookup_bits, table, max_len, first, count, offset, sorted }
+    }
+
+    /// Reads the next `width` bits in the order codes are written, without consuming them
+    fn peek<R: Read>(reader: &mut BitReader<R>, width: u64) -> io::Result<u64> {
+        let bits = reader.peeku(width)?;
+        match reader.order() {
+            BitOrder::MsbFirst => Ok(bits),
+            BitOrder::LsbFirst => Ok(reverse(bits, width)),
+        }
+    }
+
+    /// Reads the next symbol. Fails at the end of the stream or on bits that start no code.
+    pub fn decode<R: Read>(&self, reader: &mut BitReader<R>) -> io::Result<usize> {
+        let bits = HuffmanDecoder::peek(reader, self.lookup_bits)?;
+        if let Some((symbol, len)) = self.table[bits as usize] {
+            reader.readu(len)?;
+            return Ok(symbol);
+        }
+
+        let bits = HuffmanDecoder::peek(reader, self.max_len)?;
+        for len in self.lookup_bits + 1..=self.max_len {
+            let code = bits >> (self.max_len - len);
+            let index = code.wrapping_sub(self.first[len as usize]);
+            if self.count[len as usize] > 0 && index < self.count[len as usize] {
+                reader.readu(len)?;
+                return Ok(self.sorted[self.offset[len as usize] + index as usize]);
+            }
+        }
+        Err(corrupt("bits do not start a Huffman code"))
+    }
+}
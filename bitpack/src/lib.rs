@@ -1 +1,11 @@
-pub mod bitpack;
\ No newline at end of file
+pub mod bitfield;
+pub mod bitpack;
+pub mod bulk;
+pub mod codes;
+pub mod fixed;
+pub mod float;
+pub mod huffman;
+pub mod range;
+pub mod serial;
+pub mod stream;
+pub mod word;
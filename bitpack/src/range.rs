@@ -0,0 +1,335 @@
+use std::io::{self, Read, Write};
+
+use crate::bitpack;
+use crate::stream::invalid;
+
+/// Bits of precision in a probability
+const PROB_BITS: u32 = 12;
+
+/// A probability of one, in units of `2^-PROB_BITS`
+const PROB_ONE: u32 = 1 << PROB_BITS;
+
+/// The range is widened a byte at a time whenever it falls below this
+const TOP: u32 = 1 << 24;
+
+/// How fast models adapt unless told otherwise: each bit moves the
+/// probability 1/32 of the way toward it
+const DEFAULT_SHIFT: u32 = 5;
+
+/// Most bits a `BitTree` symbol may have
+pub const MAX_TREE_BITS: u64 = 16;
+
+// Struct holding the adaptive probability that the next bit in a context is 0.
+// After each bit the probability moves `2^-shift` of the way toward what was
+// seen, so a small shift adapts quickly and a large one settles more precisely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitModel {
+    zero: u32,
+    shift: u32,
+}
+
+impl Default for BitModel {
+    fn default() -> BitModel {
+        BitModel::new()
+    }
+}
+
+impl BitModel {
+    /// A model with even odds that adapts at the default rate
+    pub const fn new() -> BitModel {
+        BitModel::with_shift(DEFAULT_SHIFT)
+    }
+
+    /// A model with even odds that adapts by `2^-shift` per bit.
+    /// Panics, at compile time in a constant, unless `shift` is 1 to 11.
+    pub const fn with_shift(shift: u32) -> BitModel {
+        assert!(shift >= 1 && shift < PROB_BITS, "models adapt with a shift of 1 to 11");
+        BitModel { zero: PROB_ONE / 2, shift }
+    }
+
+    /// The estimated chance that the next bit is 0
+    pub fn probability(&self) -> f64 {
+        self.zero as f64 / PROB_ONE as f64
+    }
+
+    /// Moves the probability toward `bit`. It never reaches 0 or 1, so
+    /// either bit can always be coded.
+    fn update(&mut self, bit: bool) {
+        if bit {
+            self.zero -= self.zero >> self.shift;
+        } else {
+            self.zero += (PROB_ONE - self.zero) >> self.shift;
+        }
+    }
+}
+
+// Struct holding the models for symbols of `bits` bits, coded most
+// significant bit first with each bit in the context of the bits before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitTree {
+    bits: u64,
+    models: Vec<BitModel>,
+}
+
+impl BitTree {
+    /// Models for symbols of `bits` bits. Panics if `bits` is over `MAX_TREE_BITS`.
+    pub fn new(bits: u64) -> BitTree {
+        assert!(bits <= MAX_TREE_BITS, "bit trees have at most {} bits", MAX_TREE_BITS);
+        BitTree { bits, models: vec![BitModel::new(); 1 << bits] }
+    }
+
+    /// Number of bits in each symbol
+    pub fn bits(&self) -> u64 {
+        self.bits
+    }
+}
+
+// Struct holding the models for signed integers. A value is coded as a
+// flag for zero, its sign, the bit length of its magnitude in unary with a
+// model for each position, and the bits of the magnitude below its leading
+// one with even odds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntModel {
+    zero: BitModel,
+    sign: BitModel,
+    length: [BitModel; 64],
+}
+
+impl Default for IntModel {
+    fn default() -> IntModel {
+        IntModel::new()
+    }
+}
+
+impl IntModel {
+    /// Models with even odds that adapt at the default rate
+    pub const fn new() -> IntModel {
+        IntModel { zero: BitModel::new(), sign: BitModel::new(), length: [BitModel::new(); 64] }
+    }
+}
+
+/// A context number for a value from the magnitudes of its already coded
+/// neighbours: 0 when they are all zero, then one more for each doubling
+/// of their sum, capped at `contexts - 1`
+pub fn magnitude_context(neighbours: &[i64], contexts: usize) -> usize {
+    let sum = neighbours.iter().fold(0u64, |sum, &n| sum.saturating_add(n.unsigned_abs()));
+    ((64 - sum.leading_zeros()) as usize).min(contexts.saturating_sub(1))
+}
+
+// Struct coding bits into a byte stream with a binary range coder.
+// `low` holds the bottom of the interval with a carry bit above its low 32
+// bits. Bytes that a carry could still change wait as `cache` followed by
+// `pending` bytes of 0xFF.
+#[derive(Debug)]
+pub struct RangeEncoder<W: Write> {
+    inner: W,
+    low: u64,
+    range: u32,
+    cache: u8,
+    pending: u64,
+}
+
+impl<W: Write> RangeEncoder<W> {
+    /// Starts a stream writing to `inner`
+    pub fn new(inner: W) -> RangeEncoder<W> {
+        RangeEncoder { inner, low: 0, range: u32::MAX, cache: 0, pending: 1 }
+    }
+
+    /// Moves the top byte of `low` out, once no carry can reach it
+    fn shift_low(&mut self) -> io::Result<()> {
+        if self.low < 0xFF00_0000 || self.low > u32::MAX as u64 {
+            let carry = (self.low >> 32) as u8;
+            let mut byte = self.cache;
+            while self.pending > 0 {
+                self.inner.write_all(&[byte.wrapping_add(carry)])?;
+                byte = 0xFF;
+                self.pending -= 1;
+            }
+            self.cache = (self.low >> 24) as u8;
+        }
+        self.pending += 1;
+        self.low = (self.low & 0x00FF_FFFF) << 8;
+        Ok(())
+    }
+
+    /// Widens the range back to at least `TOP`
+    fn normalize(&mut self) -> io::Result<()> {
+        while self.range < TOP {
+            self.range <<= 8;
+            self.shift_low()?;
+        }
+        Ok(())
+    }
+
+    /// Codes `bit` with the odds of `model`, then adapts the model to it
+    pub fn encode_bit(&mut self, model: &mut BitModel, bit: bool) -> io::Result<()> {
+        let bound = (self.range >> PROB_BITS) * model.zero;
+        if bit {
+            self.low += bound as u64;
+            self.range -= bound;
+        } else {
+            self.range = bound;
+        }
+        model.update(bit);
+        self.normalize()
+    }
+
+    /// Codes `value` as `width` bits with even odds, most significant first.
+    /// Fails if the value does not fit or the width is over 64.
+    pub fn encode_direct(&mut self, width: u64, value: u64) -> io::Result<()> {
+        bitpack::new_unsigned(0, width, 0, value).map_err(invalid)?;
+        for bit in (0..width).rev() {
+            self.range >>= 1;
+            if (value >> bit) & 1 == 1 {
+                self.low += self.range as u64;
+            }
+            self.normalize()?;
+        }
+        Ok(())
+    }
+
+    /// Codes `value` with the models of `tree`. Fails if it has more bits than the tree.
+    pub fn encode_tree(&mut self, tree: &mut BitTree, value: u64) -> io::Result<()> {
+        bitpack::new_unsigned(0, tree.bits, 0, value).map_err(invalid)?;
+        let mut node = 1;
+        for bit in (0..tree.bits).rev() {
+            let bit = (value >> bit) & 1 == 1;
+            self.encode_bit(&mut tree.models[node], bit)?;
+            node = node * 2 + bit as usize;
+        }
+        Ok(())
+    }
+
+    /// Codes `value` with the models of `model`
+    pub fn encode_int(&mut self, model: &mut IntModel, value: i64) -> io::Result<()> {
+        self.encode_bit(&mut model.zero, value == 0)?;
+        if value == 0 {
+            return Ok(());
+        }
+        self.encode_bit(&mut model.sign, value < 0)?;
+
+        // The bit length as a run of ones, ended by a zero unless it is the longest
+        let magnitude = value.unsigned_abs();
+        let bits = 64 - magnitude.leading_zeros() as u64;
+        for position in 0..bits - 1 {
+            self.encode_bit(&mut model.length[position as usize], true)?;
+        }
+        if bits < 64 {
+            self.encode_bit(&mut model.length[bits as usize - 1], false)?;
+        }
+        self.encode_direct(bits - 1, bitpack::getu(magnitude, bits - 1, 0).unwrap())
+    }
+
+    /// Writes out the rest of the interval, flushes it and hands back the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        for _ in 0..5 {
+            self.shift_low()?;
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+// Struct decoding bits coded by a `RangeEncoder`. `code` is the position of
+// the coded value within the current interval.
+#[derive(Debug)]
+pub struct RangeDecoder<R: Read> {
+    inner: R,
+    range: u32,
+    code: u32,
+}
+
+impl<R: Read> RangeDecoder<R> {
+    /// Starts a stream reading from `inner`. Fails if it is too short to hold one.
+    pub fn new(inner: R) -> io::Result<RangeDecoder<R>> {
+        let mut decoder = RangeDecoder { inner, range: u32::MAX, code: 0 };
+
+        // The encoder's first byte is always 0, and the next four fill `code`
+        for _ in 0..5 {
+            decoder.code = (decoder.code << 8) | decoder.next_byte()? as u32;
+        }
+        Ok(decoder)
+    }
+
+    /// Reads the next byte of the stream
+    fn next_byte(&mut self) -> io::Result<u8> {
+        let mut byte = [0u8];
+        self.inner.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    /// Widens the range back to at least `TOP`
+    fn normalize(&mut self) -> io::Result<()> {
+        while self.range < TOP {
+            self.range <<= 8;
+            self.code = (self.code << 8) | self.next_byte()? as u32;
+        }
+        Ok(())
+    }
+
+    /// Decodes a bit with the odds of `model`, then adapts the model to it.
+    /// Fails at the end of the stream.
+    pub fn decode_bit(&mut self, model: &mut BitModel) -> io::Result<bool> {
+        let bound = (self.range >> PROB_BITS) * model.zero;
+        let bit = self.code >= bound;
+        if bit {
+            self.code -= bound;
+            self.range -= bound;
+        } else {
+            self.range = bound;
+        }
+        model.update(bit);
+        self.normalize()?;
+        Ok(bit)
+    }
+
+    /// Decodes `width` bits with even odds, most significant first.
+    /// Fails at the end of the stream or if the width is over 64.
+    pub fn decode_direct(&mut self, width: u64) -> io::Result<u64> {
+        bitpack::check_field(width, 0).map_err(invalid)?;
+        let mut value = 0u64;
+        for _ in 0..width {
+            self.range >>= 1;
+            let bit = self.code >= self.range;
+            if bit {
+                self.code -= self.range;
+            }
+            value = (value << 1) | bit as u64;
+            self.normalize()?;
+        }
+        Ok(value)
+    }
+
+    /// Decodes a symbol with the models of `tree`
+    pub fn decode_tree(&mut self, tree: &mut BitTree) -> io::Result<u64> {
+        let mut node = 1;
+        for _ in 0..tree.bits {
+            let bit = self.decode_bit(&mut tree.models[node])?;
+            node = node * 2 + bit as usize;
+        }
+        Ok(node as u64 - (1 << tree.bits))
+    }
+
+    /// Decodes a signed integer with the models of `model`
+    pub fn decode_int(&mut self, model: &mut IntModel) -> io::Result<i64> {
+        if self.decode_bit(&mut model.zero)? {
+            return Ok(0);
+        }
+        let negative = self.decode_bit(&mut model.sign)?;
+
+        let mut bits = 1;
+        while bits < 64 && self.decode_bit(&mut model.length[bits as usize - 1])? {
+            bits += 1;
+        }
+        let magnitude = (1u64 << (bits - 1)) | self.decode_direct(bits - 1)?;
+
+        // Only the magnitude of the most negative value is past i64::MAX
+        let value = if negative { 0i64.checked_sub_unsigned(magnitude) } else { i64::try_from(magnitude).ok() };
+        value.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "integer does not fit in 64 bits"))
+    }
+
+    /// Hands back the inner reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
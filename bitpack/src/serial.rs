@@ -0,0 +1,94 @@
+use crate::bitpack::BitpackError;
+use crate::stream::{BitOrder, BitReader, BitWriter};
+use crate::word::Word;
+
+// Enum listing the orders words are written to bytes in.
+// Words of whole bytes are written a byte at a time, most significant byte
+// first for `BigEndian` and least significant first for `LittleEndian`.
+// Other widths are packed with no padding between words, in the bit order
+// of the same name: `BigEndian` fills bytes from their most significant bit
+// and `LittleEndian` from their least significant bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ByteOrder {
+    #[default]
+    BigEndian,
+    LittleEndian,
+}
+
+impl ByteOrder {
+    /// The bit stream order that writes words in this byte order
+    pub fn bit_order(self) -> BitOrder {
+        match self {
+            ByteOrder::BigEndian => BitOrder::MsbFirst,
+            ByteOrder::LittleEndian => BitOrder::LsbFirst,
+        }
+    }
+}
+
+/// Bytes taken by `count` words of `bits` bits, including the padding after the last
+pub fn byte_len(count: usize, bits: u64) -> usize {
+    (count as u64 * bits).div_ceil(8) as usize
+}
+
+/// The low and high 64-bit halves of a word of up to 128 bits, with the width of each
+fn halves<W: Word>(word: W, bits: u64) -> [(u64, u64); 2] {
+    let low = bits.min(64);
+    let high = if bits > 64 { (word >> 64).low_u64() } else { 0 };
+    [(low, word.low_u64()), (bits - low, high)]
+}
+
+/// Writes each word as `bits` bits in `order`, padding the last byte with zeros.
+/// Fails if `bits` is wider than `W` or a word does not fit in `bits` bits.
+pub fn words_to_bytes<W: Word>(words: &[W], bits: u64, order: ByteOrder) -> Result<Vec<u8>, BitpackError> {
+    W::check_field(bits, 0)?;
+    if words.iter().any(|word| !word.fits_unsigned(bits)) {
+        return Err(BitpackError::ValueDoesNotFit { width: bits });
+    }
+
+    let mut writer = BitWriter::new(Vec::with_capacity(byte_len(words.len(), bits)), order.bit_order());
+    for &word in words {
+        let [low, high] = halves(word, bits);
+
+        // The more significant half goes first in big-endian order
+        let parts = match order {
+            ByteOrder::BigEndian => [high, low],
+            ByteOrder::LittleEndian => [low, high],
+        };
+        for (width, value) in parts {
+            writer.writeu(width, value).expect("writing to a Vec cannot fail");
+        }
+    }
+    Ok(writer.finish().expect("writing to a Vec cannot fail"))
+}
+
+/// Reads as many words of `bits` bits in `order` as `bytes` holds whole.
+/// Padding of a byte or more after the last word would be read as extra
+/// words, so narrow words need their count stored elsewhere.
+/// Words of no bits take no space, so none are read.
+/// Fails if `bits` is wider than `W`.
+pub fn words_from_bytes<W: Word>(bytes: &[u8], bits: u64, order: ByteOrder) -> Result<Vec<W>, BitpackError> {
+    W::check_field(bits, 0)?;
+    if bits == 0 {
+        return Ok(Vec::new());
+    }
+
+    let count = (bytes.len() as u64 * 8 / bits) as usize;
+    let mut reader = BitReader::new(bytes, order.bit_order());
+    let mut read = |width| reader.readu(width).expect("the bytes hold every whole word");
+    let words = (0..count)
+        .map(|_| {
+            let (low, high) = (bits.min(64), bits.saturating_sub(64));
+            let (low, high) = match order {
+                ByteOrder::BigEndian => {
+                    let high = read(high);
+                    (read(low), high)
+                }
+                ByteOrder::LittleEndian => (read(low), read(high)),
+            };
+
+            // Only words of more than 64 bits have a high half
+            if bits > 64 { W::from_u64(high) << 64 | W::from_u64(low) } else { W::from_u64(low) }
+        })
+        .collect();
+    Ok(words)
+}
@@ -0,0 +1,248 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+use crate::bitpack::{self, BitpackError};
+
+// Enum listing the orders bits are packed into bytes.
+// `MsbFirst` fills each byte from its most significant bit and writes fields
+// most significant bit first, like most image formats. `LsbFirst` fills each
+// byte from its least significant bit and writes fields least significant
+// bit first, like DEFLATE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitOrder {
+    #[default]
+    MsbFirst,
+    LsbFirst,
+}
+
+// Struct appending fields of any width to a byte stream.
+// Whole bytes are passed on to `inner` as soon as they are filled.
+#[derive(Debug)]
+pub struct BitWriter<W: Write> {
+    inner: W,
+    order: BitOrder,
+    byte: u8,
+    used: u64,
+}
+
+// Struct reading fields of any width from a byte stream.
+// Bytes taken from `inner` by `peeku` wait in `ahead` until they are read.
+#[derive(Debug)]
+pub struct BitReader<R: Read> {
+    inner: R,
+    order: BitOrder,
+    byte: u8,
+    used: u64,
+    ahead: VecDeque<u8>,
+}
+
+/// The low `n` bits of `value`, for `n` of at most 8
+fn low_bits(value: u64, n: u64) -> u8 {
+    (value & ((1 << n) - 1)) as u8
+}
+
+/// The error for a field that cannot be written or read
+pub(crate) fn invalid(error: BitpackError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, error)
+}
+
+impl<W: Write> BitWriter<W> {
+    /// Starts a stream writing to `inner` in the given bit order
+    pub fn new(inner: W, order: BitOrder) -> BitWriter<W> {
+        BitWriter { inner, order, byte: 0, used: 0 }
+    }
+
+    /// The bit order of the stream
+    pub fn order(&self) -> BitOrder {
+        self.order
+    }
+
+    /// Appends `value` as a field of `width` unsigned bits.
+    /// Fails if the value does not fit or the width is over 64.
+    pub fn writeu(&mut self, width: u64, value: u64) -> io::Result<()> {
+        bitpack::new_unsigned(0, width, 0, value).map_err(invalid)?;
+
+        let mut left = width;
+        while left > 0 {
+            // Fill as much of the current byte as the field has left
+            let n = left.min(8 - self.used);
+            match self.order {
+                BitOrder::MsbFirst => {
+                    let bits = low_bits(value >> (left - n), n);
+                    self.byte |= bits << (8 - self.used - n);
+                }
+                BitOrder::LsbFirst => {
+                    let bits = low_bits(value >> (width - left), n);
+                    self.byte |= bits << self.used;
+                }
+            }
+            self.used += n;
+            left -= n;
+
+            if self.used == 8 {
+                self.inner.write_all(&[self.byte])?;
+                self.byte = 0;
+                self.used = 0;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Appends `value` as a field of `width` signed bits in two's complement.
+    /// Fails if the value does not fit or the width is over 64.
+    pub fn writes(&mut self, width: u64, value: i64) -> io::Result<()> {
+        let bits = bitpack::new_signed(0, width, 0, value).map_err(invalid)?;
+        self.writeu(width, bits)
+    }
+
+    /// Pads the current byte with zero bits so the next field starts on a byte boundary
+    pub fn align(&mut self) -> io::Result<()> {
+        if self.used > 0 {
+            self.writeu(8 - self.used, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Pads the last byte with zero bits, flushes it and hands back the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.align()?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<R: Read> BitReader<R> {
+    /// Starts a stream reading from `inner` in the given bit order
+    pub fn new(inner: R, order: BitOrder) -> BitReader<R> {
+        BitReader { inner, order, byte: 0, used: 8, ahead: VecDeque::new() }
+    }
+
+    /// The bit order of the stream
+    pub fn order(&self) -> BitOrder {
+        self.order
+    }
+
+    /// Reads a field of `width` unsigned bits.
+    /// Fails at the end of the stream or if the width is over 64.
+    pub fn readu(&mut self, width: u64) -> io::Result<u64> {
+        bitpack::check_field(width, 0).map_err(invalid)?;
+
+        let mut value = 0u64;
+        let mut got = 0;
+        while got < width {
+            if self.used == 8 {
+                self.byte = match self.ahead.pop_front() {
+                    Some(byte) => byte,
+                    None => {
+                        let mut byte = [0u8];
+                        self.inner.read_exact(&mut byte)?;
+                        byte[0]
+                    }
+                };
+                self.used = 0;
+            }
+
+            // Take as much of the current byte as the field still needs
+            let n = (width - got).min(8 - self.used);
+            match self.order {
+                BitOrder::MsbFirst => {
+                    let bits = low_bits((self.byte >> (8 - self.used - n)) as u64, n);
+                    value = (value << n) | bits as u64;
+                }
+                BitOrder::LsbFirst => {
+                    let bits = low_bits((self.byte >> self.used) as u64, n);
+                    value |= (bits as u64) << got;
+                }
+            }
+            self.used += n;
+            got += n;
+        }
+
+        Ok(value)
+    }
+
+    /// Returns the next field of `width` unsigned bits without reading it.
+    /// Bits past the end of the stream are taken as zeros.
+    /// Fails if the width is over 64.
+    pub fn peeku(&mut self, width: u64) -> io::Result<u64> {
+        bitpack::check_field(width, 0).map_err(invalid)?;
+
+        // Take in enough whole bytes to cover the field, or all that are left
+        while (8 - self.used) + 8 * (self.ahead.len() as u64) < width {
+            let mut byte = [0u8];
+            match self.inner.read_exact(&mut byte) {
+                Ok(()) => self.ahead.push_back(byte[0]),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let rest = (self.byte as u64, self.used);
+        let mut value = 0u64;
+        let mut got = 0;
+        for (byte, used) in std::iter::once(rest).chain(self.ahead.iter().map(|&byte| (byte as u64, 0))) {
+            let n = (width - got).min(8 - used);
+            match self.order {
+                BitOrder::MsbFirst => value = (value << n) | low_bits(byte >> (8 - used - n), n) as u64,
+                BitOrder::LsbFirst => value |= (low_bits(byte >> used, n) as u64) << got,
+            }
+            got += n;
+        }
+
+        // Missing bits at the end of a most significant first field are its low bits
+        match self.order {
+            BitOrder::MsbFirst => Ok(value.checked_shl((width - got) as u32).unwrap_or(0)),
+            BitOrder::LsbFirst => Ok(value),
+        }
+    }
+
+    /// Reads a field of `width` signed bits in two's complement.
+    /// Fails at the end of the stream or if the width is over 64.
+    pub fn reads(&mut self, width: u64) -> io::Result<i64> {
+        let bits = self.readu(width)?;
+        bitpack::get_signed(bits, width, 0).map_err(invalid)
+    }
+
+    /// Skips the rest of the current byte so the next field starts on a byte boundary
+    pub fn align(&mut self) {
+        self.used = 8;
+    }
+
+    /// Hands back the inner reader. Bits left in the current byte, and bytes
+    /// taken in by `peeku`, are lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+// Bytes written through `Write` are appended as 8-bit fields, so they need
+// not start on a byte boundary
+impl<W: Write> Write for BitWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.writeu(8, byte as u64)?;
+        }
+        Ok(buf.len())
+    }
+
+    /// Flushes the inner writer. A partly filled byte stays in the stream.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Bytes read through `Read` are taken as 8-bit fields, so they need not
+// start on a byte boundary. A partial byte at the end of the stream is not returned.
+impl<R: Read> Read for BitReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        for (count, slot) in buf.iter_mut().enumerate() {
+            match self.readu(8) {
+                Ok(byte) => *slot = byte as u8,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(count),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(buf.len())
+    }
+}
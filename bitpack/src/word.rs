@@ -0,0 +1,148 @@
+use std::fmt::Debug;
+use std::ops::{BitAnd, BitOr, Not, Shl, Shr};
+
+use crate::bitpack::BitpackError;
+
+// Trait for the unsigned integer types fields can be packed into.
+// Widths and LSBs are checked against `BITS`, so a field that fits a `u64`
+// can still be rejected for a `u32`. Signed fields are stored in two's
+// complement and read back as `Signed`, the signed type of the same size.
+pub trait Word:
+    Copy
+    + Eq
+    + Debug
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + Not<Output = Self>
+    + Shl<u64, Output = Self>
+    + Shr<u64, Output = Self>
+    + TryFrom<u64>
+    + TryInto<u64>
+{
+    /// The signed integer type of the same size
+    type Signed: Copy + Eq + Debug + TryFrom<i64> + TryInto<i64>;
+
+    /// Number of bits in a word
+    const BITS: u64;
+    /// The word with no bits set
+    const ZERO: Self;
+    /// The word with every bit set
+    const ONES: Self;
+
+    /// The bits of the word as a signed value
+    fn to_signed(self) -> Self::Signed;
+
+    /// The two's complement bits of a signed value
+    fn from_signed(value: Self::Signed) -> Self;
+
+    /// The low 64 bits of the word
+    fn low_u64(self) -> u64;
+
+    /// A word holding the bits of `value` that fit in it
+    fn from_u64(value: u64) -> Self;
+
+    /// A mask of the low `width` bits, which is every bit once `width` reaches `BITS`
+    fn low_mask(width: u64) -> Self {
+        if width >= Self::BITS { Self::ONES } else { !(Self::ONES << width) }
+    }
+
+    /// Copies bit `width - 1` of `bits` into every bit above it
+    fn sign_extend(bits: Self, width: u64) -> Self {
+        if width > 0 && (bits >> (width - 1)) & Self::low_mask(1) != Self::ZERO {
+            bits | !Self::low_mask(width)
+        } else {
+            bits
+        }
+    }
+
+    /// Checks that a field of `width` bits starting at `lsb` lies within a word
+    fn check_field(width: u64, lsb: u64) -> Result<(), BitpackError> {
+        if width > Self::BITS {
+            return Err(BitpackError::WidthTooLarge { width });
+        }
+        if lsb > Self::BITS - width {
+            return Err(BitpackError::FieldOutOfWord { width, lsb });
+        }
+        Ok(())
+    }
+
+    /// True iff the word, taken as an unsigned value, fits in `width` bits
+    fn fits_unsigned(self, width: u64) -> bool {
+        self & !Self::low_mask(width) == Self::ZERO
+    }
+
+    /// True iff `value` fits in `width` signed bits
+    fn fits_signed(value: Self::Signed, width: u64) -> bool {
+        let bits = Self::from_signed(value);
+        if width == 0 {
+            return bits == Self::ZERO;
+        }
+        Self::sign_extend(bits & Self::low_mask(width), width) == bits
+    }
+
+    /// Reads the unsigned field of `width` bits at `lsb`
+    fn get_unsigned(self, width: u64, lsb: u64) -> Result<Self, BitpackError> {
+        Self::check_field(width, lsb)?;
+        if width == 0 {
+            return Ok(Self::ZERO);
+        }
+        Ok((self >> lsb) & Self::low_mask(width))
+    }
+
+    /// Reads the signed field of `width` bits at `lsb`, sign extending it
+    fn get_signed(self, width: u64, lsb: u64) -> Result<Self::Signed, BitpackError> {
+        let bits = self.get_unsigned(width, lsb)?;
+        Ok(Self::sign_extend(bits, width).to_signed())
+    }
+
+    /// The word with the field of `width` bits at `lsb` replaced by the unsigned `value`
+    fn new_unsigned(self, width: u64, lsb: u64, value: Self) -> Result<Self, BitpackError> {
+        Self::check_field(width, lsb)?;
+        if !value.fits_unsigned(width) {
+            return Err(BitpackError::ValueDoesNotFit { width });
+        }
+        if width == 0 {
+            return Ok(self);
+        }
+        Ok((self & !(Self::low_mask(width) << lsb)) | (value << lsb))
+    }
+
+    /// The word with the field of `width` bits at `lsb` replaced by the signed `value`
+    fn new_signed(self, width: u64, lsb: u64, value: Self::Signed) -> Result<Self, BitpackError> {
+        Self::check_field(width, lsb)?;
+        if !Self::fits_signed(value, width) {
+            return Err(BitpackError::ValueDoesNotFit { width });
+        }
+        self.new_unsigned(width, lsb, Self::from_signed(value) & Self::low_mask(width))
+    }
+}
+
+macro_rules! impl_word {
+    ($($word:ty => $signed:ty),*) => {$(
+        impl Word for $word {
+            type Signed = $signed;
+
+            const BITS: u64 = <$word>::BITS as u64;
+            const ZERO: $word = 0;
+            const ONES: $word = <$word>::MAX;
+
+            fn to_signed(self) -> $signed {
+                self as $signed
+            }
+
+            fn from_signed(value: $signed) -> $word {
+                value as $word
+            }
+
+            fn low_u64(self) -> u64 {
+                self as u64
+            }
+
+            fn from_u64(value: u64) -> $word {
+                value as $word
+            }
+        }
+    )*};
+}
+
+impl_word!(u8 => i8, u16 => i16, u32 => i32, u64 => i64, u128 => i128);
@@ -0,0 +1,66 @@
+use bitpack::bitfield;
+use bitpack::bitfield::FieldSpec;
+use bitpack::bitpack::BitpackError;
+
+bitfield! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Sample: u32 {
+        tag: unsigned(3, 29),
+        delta: signed(13, 16),
+        level: unsigned(16, 0),
+    }
+}
+
+bitfield! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Wide: u128 {
+        pub high: signed(64, 64),
+        pub low: unsigned(64, 0),
+    }
+}
+
+// Fields may leave gaps and need not be declared in bit order
+bitfield! {
+    #[derive(Debug, Default, PartialEq, Eq)]
+    struct Sparse: u8 {
+        flag: unsigned(1, 7),
+        nibble: unsigned(4, 0),
+    }
+}
+
+#[test]
+fn fields_are_described_in_declaration_order() {
+    assert_eq!(
+        Sample::FIELDS,
+        [
+            FieldSpec { name: "tag", width: 3, lsb: 29, signed: false },
+            FieldSpec { name: "delta", width: 13, lsb: 16, signed: true },
+            FieldSpec { name: "level", width: 16, lsb: 0, signed: false },
+        ]
+    );
+}
+
+#[test]
+fn structs_round_trip_through_their_word() {
+    let sample = Sample { tag: 5, delta: -4096, level: 0xBEEF };
+    let word = sample.pack().unwrap();
+    assert_eq!(word, 0xB000_BEEF);
+    assert_eq!(Sample::unpack(word), sample);
+
+    let wide = Wide { high: i64::MIN, low: u64::MAX };
+    assert_eq!(wide.pack(), Ok(1 << 127 | u128::from(u64::MAX)));
+    assert_eq!(Wide::unpack(wide.pack().unwrap()), wide);
+
+    assert_eq!(Sparse { flag: 1, nibble: 9 }.pack(), Ok(0x89));
+    assert_eq!(Sparse::unpack(0x7F), Sparse { flag: 0, nibble: 0xF });
+}
+
+#[test]
+fn values_that_do_not_fit_are_reported() {
+    let sample = Sample { tag: 8, delta: 0, level: 0 };
+    assert_eq!(sample.pack(), Err(BitpackError::ValueDoesNotFit { width: 3 }));
+    let sample = Sample { tag: 0, delta: 4096, level: 0 };
+    assert_eq!(sample.pack(), Err(BitpackError::ValueDoesNotFit { width: 13 }));
+    let sample = Sample { tag: 0, delta: 0, level: 1 << 40 };
+    assert_eq!(sample.pack(), Err(BitpackError::ValueDoesNotFit { width: 16 }));
+}
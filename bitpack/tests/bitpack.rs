@@ -0,0 +1,117 @@
+use bitpack::bitpack::{
+    check_field, fitss, fitsu, get_signed, get_unsigned, gets, getu, new_signed, new_unsigned, news, newu, BitpackError,
+};
+use proptest::prelude::*;
+
+/// The bits covered by a field that lies within the word
+fn field_mask(width: u64, lsb: u64) -> u64 {
+    if width == 0 { 0 } else { u64::MAX >> (64 - width) << lsb }
+}
+
+/// The smallest and largest values of a signed field
+fn signed_range(width: u64) -> (i64, i64) {
+    match width {
+        0 => (0, 0),
+        64 => (i64::MIN, i64::MAX),
+        _ => (-(1 << (width - 1)), (1 << (width - 1)) - 1),
+    }
+}
+
+/// The largest value of an unsigned field
+fn unsigned_max(width: u64) -> u64 {
+    if width == 0 { 0 } else { u64::MAX >> (64 - width) }
+}
+
+#[test]
+fn every_field_within_a_word_round_trips_its_extremes() {
+    let word = 0xA5A5_5A5A_F00F_0FF0;
+    for width in 0..=64 {
+        for lsb in 0..=64 - width {
+            let mask = field_mask(width, lsb);
+
+            for value in [0, 1, unsigned_max(width) / 2, unsigned_max(width)] {
+                let value = value.min(unsigned_max(width));
+                let new = new_unsigned(word, width, lsb, value).unwrap();
+                assert_eq!(get_unsigned(new, width, lsb), Ok(value), "width {} lsb {}", width, lsb);
+                assert_eq!(new & !mask, word & !mask, "width {} lsb {}", width, lsb);
+            }
+
+            let (min, max) = signed_range(width);
+            for value in [min, max, 0, (-1i64).max(min), 1i64.min(max)] {
+                let new = new_signed(word, width, lsb, value).unwrap();
+                assert_eq!(get_signed(new, width, lsb), Ok(value), "width {} lsb {}", width, lsb);
+                assert_eq!(new & !mask, word & !mask, "width {} lsb {}", width, lsb);
+            }
+        }
+    }
+}
+
+#[test]
+fn values_one_past_the_extremes_do_not_fit() {
+    for width in 0..64 {
+        let (min, max) = signed_range(width);
+        let too_big = BitpackError::ValueDoesNotFit { width };
+        assert_eq!(new_unsigned(0, width, 0, unsigned_max(width) + 1), Err(too_big));
+        assert_eq!(new_signed(0, width, 0, max + 1), Err(too_big));
+        assert_eq!(new_signed(0, width, 0, min - 1), Err(too_big));
+        assert!(!fitsu(unsigned_max(width) + 1, width));
+        assert!(!fitss(max + 1, width) && !fitss(min - 1, width));
+    }
+    assert!(fitsu(u64::MAX, 64) && fitss(i64::MIN, 64) && fitss(i64::MAX, 64));
+}
+
+#[test]
+fn errors_name_the_reason() {
+    assert_eq!(check_field(65, 0), Err(BitpackError::WidthTooLarge { width: 65 }));
+    assert_eq!(check_field(8, 57), Err(BitpackError::FieldOutOfWord { width: 8, lsb: 57 }));
+    assert_eq!(check_field(0, 64), Ok(()));
+    assert_eq!(check_field(0, 65), Err(BitpackError::FieldOutOfWord { width: 0, lsb: 65 }));
+    assert_eq!(get_unsigned(0, 1, 64), Err(BitpackError::FieldOutOfWord { width: 1, lsb: 64 }));
+    assert_eq!(get_signed(0, 100, 0), Err(BitpackError::WidthTooLarge { width: 100 }));
+
+    // A field outside the word is reported before a value that does not fit
+    assert_eq!(new_unsigned(0, 4, 62, 100), Err(BitpackError::FieldOutOfWord { width: 4, lsb: 62 }));
+    assert_eq!(new_signed(0, 4, 0, 8), Err(BitpackError::ValueDoesNotFit { width: 4 }));
+}
+
+#[test]
+fn signed_fields_overwrite_what_was_there() {
+    let word = new_signed(u64::MAX, 5, 8, -16).unwrap();
+    assert_eq!(gets(word, 5, 8), Some(-16));
+    assert_eq!(news(word, 5, 8, 3).and_then(|word| gets(word, 5, 8)), Some(3));
+}
+
+proptest! {
+    #[test]
+    fn option_functions_agree_with_checked_ones(word: u64, width in 0u64..=70, lsb in 0u64..=70, value: u64, signed: i64) {
+        prop_assert_eq!(getu(word, width, lsb), get_unsigned(word, width, lsb).ok());
+        prop_assert_eq!(gets(word, width, lsb), get_signed(word, width, lsb).ok());
+        prop_assert_eq!(newu(word, width, lsb, value), new_unsigned(word, width, lsb, value).ok());
+        prop_assert_eq!(news(word, width, lsb, signed), new_signed(word, width, lsb, signed).ok());
+    }
+
+    #[test]
+    fn unsigned_fields_round_trip(word: u64, width in 0u64..=64, lsb in 0u64..=64, value: u64) {
+        prop_assume!(width + lsb <= 64);
+        let value = value & unsigned_max(width);
+        let new = new_unsigned(word, width, lsb, value).unwrap();
+        prop_assert_eq!(get_unsigned(new, width, lsb), Ok(value));
+        prop_assert_eq!(new & !field_mask(width, lsb), word & !field_mask(width, lsb));
+    }
+
+    #[test]
+    fn signed_fields_round_trip(word: u64, width in 1u64..=64, lsb in 0u64..=63, value: i64) {
+        prop_assume!(width + lsb <= 64);
+        let value = value >> (64 - width);
+        let new = new_signed(word, width, lsb, value).unwrap();
+        prop_assert_eq!(get_signed(new, width, lsb), Ok(value));
+        prop_assert_eq!(new & !field_mask(width, lsb), word & !field_mask(width, lsb));
+    }
+
+    #[test]
+    fn fitting_matches_the_range(width in 0u64..=64, value: u64, signed: i64) {
+        let (min, max) = signed_range(width);
+        prop_assert_eq!(fitsu(value, width), value <= unsigned_max(width));
+        prop_assert_eq!(fitss(signed, width), (min..=max).contains(&signed));
+    }
+}
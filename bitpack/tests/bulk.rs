@@ -0,0 +1,73 @@
+use bitpack::bitpack::{gets, getu, news, newu, BitpackError};
+use bitpack::bulk::{pack_signed, pack_unsigned, unpack_signed, unpack_unsigned};
+use bitpack::word::Word;
+use proptest::prelude::*;
+
+#[test]
+fn fields_are_checked_against_the_word() {
+    let words = [0u32; 4];
+    assert_eq!(unpack_unsigned(&words, 4, 29), Err(BitpackError::FieldOutOfWord { width: 4, lsb: 29 }));
+    assert_eq!(unpack_signed(&words, 33, 0), Err(BitpackError::WidthTooLarge { width: 33 }));
+    assert_eq!(unpack_unsigned(&[0u128], 65, 0), Err(BitpackError::WidthTooLarge { width: 65 }));
+    assert_eq!(unpack_unsigned(&words, 0, 32), Ok(vec![0; 4]));
+}
+
+#[test]
+fn nothing_is_written_unless_every_value_fits() {
+    let mut words = [0xFFu8; 3];
+    assert_eq!(pack_unsigned(&mut words, 3, 2, &[1, 8, 0]), Err(BitpackError::ValueDoesNotFit { width: 3 }));
+    assert_eq!(pack_signed(&mut words, 3, 2, &[-4, 4, 0]), Err(BitpackError::ValueDoesNotFit { width: 3 }));
+    assert_eq!(pack_signed(&mut words, 0, 0, &[0, 1, 0]), Err(BitpackError::ValueDoesNotFit { width: 0 }));
+    assert_eq!(words, [0xFF; 3]);
+
+    assert_eq!(pack_signed(&mut words, 3, 2, &[-4, 3, 0]), Ok(()));
+    assert_eq!(words, [0xF3, 0xEF, 0xE3]);
+    assert_eq!(unpack_signed(&words, 3, 2), Ok(vec![-4, 3, 0]));
+}
+
+#[test]
+fn wide_fields_of_u128_words() {
+    let mut words = [u128::MAX, 0];
+    pack_signed(&mut words, 64, 64, &[1, i64::MIN]).unwrap();
+    assert_eq!(words, [(1 << 64) | u128::from(u64::MAX), 1 << 127]);
+    assert_eq!(unpack_signed(&words, 64, 64), Ok(vec![1, i64::MIN]));
+    assert_eq!(unpack_unsigned(&words, 64, 0), Ok(vec![u64::MAX, 0]));
+}
+
+#[test]
+#[should_panic(expected = "every word needs a value")]
+fn slices_must_match() {
+    pack_unsigned(&mut [0u64; 2], 4, 0, &[1]).unwrap();
+}
+
+proptest! {
+    #[test]
+    fn bulk_and_per_word_paths_agree(words: Vec<u64>, width in 0u64..=64, lsb in 0u64..=64, seed: u64) {
+        prop_assume!(width + lsb <= 64);
+        let unsigned: Vec<u64> = words.iter().map(|&word| getu(word, width, lsb).unwrap()).collect();
+        let signed: Vec<i64> = words.iter().map(|&word| gets(word, width, lsb).unwrap()).collect();
+        prop_assert_eq!(unpack_unsigned(&words, width, lsb), Ok(unsigned.clone()));
+        prop_assert_eq!(unpack_signed(&words, width, lsb), Ok(signed.clone()));
+
+        // Write the fields of other words into these ones
+        let base: Vec<u64> = words.iter().map(|&word| word ^ seed).collect();
+        let mut packed = base.clone();
+        pack_unsigned(&mut packed, width, lsb, &unsigned).unwrap();
+        let expected: Vec<u64> = base.iter().zip(&unsigned).map(|(&word, &value)| newu(word, width, lsb, value).unwrap()).collect();
+        prop_assert_eq!(&packed, &expected);
+
+        let mut packed = base.clone();
+        pack_signed(&mut packed, width, lsb, &signed).unwrap();
+        let expected: Vec<u64> = base.iter().zip(&signed).map(|(&word, &value)| news(word, width, lsb, value).unwrap()).collect();
+        prop_assert_eq!(&packed, &expected);
+    }
+
+    #[test]
+    fn u16_words_agree_with_the_word_trait(words: Vec<u16>, width in 0u64..=16, lsb in 0u64..=16) {
+        prop_assume!(width + lsb <= 16);
+        let unsigned: Vec<u64> = words.iter().map(|&word| u64::from(word.get_unsigned(width, lsb).unwrap())).collect();
+        let signed: Vec<i64> = words.iter().map(|&word| i64::from(word.get_signed(width, lsb).unwrap())).collect();
+        prop_assert_eq!(unpack_unsigned(&words, width, lsb), Ok(unsigned));
+        prop_assert_eq!(unpack_signed(&words, width, lsb), Ok(signed));
+    }
+}
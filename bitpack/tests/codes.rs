@@ -0,0 +1,143 @@
+use std::io::{self, ErrorKind};
+
+use bitpack::codes::{unzigzag, zigzag};
+use bitpack::stream::{BitOrder, BitReader, BitWriter};
+use proptest::prelude::*;
+
+/// Values near every power of two, plus the extremes
+fn interesting_values() -> Vec<u64> {
+    let mut values = vec![0, 1, 2, 3, u64::MAX - 1, u64::MAX];
+    for shift in 1..64 {
+        let power = 1u64 << shift;
+        values.extend([power - 1, power, power + 1]);
+    }
+    values
+}
+
+/// Writes values with `write`, then reads them back with `read`
+fn round_trip<T: Copy + PartialEq + std::fmt::Debug>(
+    order: BitOrder,
+    values: &[T],
+    write: impl Fn(&mut BitWriter<Vec<u8>>, T) -> io::Result<()>,
+    read: impl Fn(&mut BitReader<&[u8]>) -> io::Result<T>,
+) {
+    let mut writer = BitWriter::new(Vec::new(), order);
+    for &value in values {
+        write(&mut writer, value).unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+
+    let mut reader = BitReader::new(&bytes[..], order);
+    for &value in values {
+        assert_eq!(read(&mut reader).unwrap(), value, "{:?}", order);
+    }
+}
+
+/// The bits written by `write`, as a string of 0s and 1s padded to whole bytes
+fn bits(write: impl Fn(&mut BitWriter<Vec<u8>>) -> io::Result<()>) -> String {
+    let mut writer = BitWriter::new(Vec::new(), BitOrder::MsbFirst);
+    write(&mut writer).unwrap();
+    writer.finish().unwrap().iter().map(|byte| format!("{:08b}", byte)).collect()
+}
+
+#[test]
+fn codes_have_their_textbook_bits() {
+    assert_eq!(bits(|w| w.write_gamma(1)), "10000000");
+    assert_eq!(bits(|w| w.write_gamma(5)), "00101000");
+    assert_eq!(bits(|w| w.write_delta(1)), "10000000");
+    assert_eq!(bits(|w| w.write_delta(10)), "00100010");
+    assert_eq!(bits(|w| w.write_exp_golomb(0, 0)), "10000000");
+    assert_eq!(bits(|w| w.write_exp_golomb(0, 3)), "00100000");
+    assert_eq!(bits(|w| w.write_exp_golomb(2, 3)), "11100000");
+    assert_eq!(bits(|w| w.write_exp_golomb(2, 4)), "01000000");
+    assert_eq!(bits(|w| w.write_rice(2, 9)), "00101000");
+    assert_eq!(bits(|w| w.write_leb128(624_485)), "111001011000111000100110");
+    assert_eq!(bits(|w| w.write_sleb128(-123_456)), "110000001011101101111000");
+}
+
+#[test]
+fn every_code_round_trips_in_both_orders() {
+    let values = interesting_values();
+    let nonzero: Vec<u64> = values.iter().copied().filter(|&value| value != 0).collect();
+    let signed: Vec<i64> = values.iter().flat_map(|&value| [value as i64, (value as i64).wrapping_neg()]).collect();
+
+    for order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+        for k in [0, 1, 5, 32, 63, 64] {
+            round_trip(order, &values, |w, v| w.write_exp_golomb(k, v), |r| r.read_exp_golomb(k));
+        }
+        for k in [48, 63, 64] {
+            round_trip(order, &values, |w, v| w.write_rice(k, v), |r| r.read_rice(k));
+        }
+        round_trip(order, &[0, 1, 2, 1000, 1 << 16], |w, v| w.write_rice(0, v), |r| r.read_rice(0));
+        round_trip(order, &nonzero, |w, v| w.write_gamma(v), |r| r.read_gamma());
+        round_trip(order, &nonzero, |w, v| w.write_delta(v), |r| r.read_delta());
+        round_trip(order, &values, |w, v| w.write_leb128(v), |r| r.read_leb128());
+        round_trip(order, &signed, |w, v| w.write_sleb128(v), |r| r.read_sleb128());
+    }
+}
+
+#[test]
+fn zigzag_interleaves_signs() {
+    assert_eq!([0, -1, 1, -2, 2].map(zigzag), [0, 1, 2, 3, 4]);
+    assert_eq!(zigzag(i64::MAX), u64::MAX - 1);
+    assert_eq!(zigzag(i64::MIN), u64::MAX);
+    for value in [0, -1, 1, i64::MIN, i64::MAX, -123_456] {
+        assert_eq!(unzigzag(zigzag(value)), value);
+    }
+}
+
+#[test]
+fn bad_values_and_codes_are_errors() {
+    let mut writer = BitWriter::new(Vec::new(), BitOrder::MsbFirst);
+    assert_eq!(writer.write_gamma(0).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(writer.write_delta(0).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(writer.write_rice(65, 0).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(writer.write_exp_golomb(65, 0).unwrap_err().kind(), ErrorKind::InvalidInput);
+
+    // Long runs of zeros, or of continuation bits, would need more than 64 bits
+    let zeros = [0u8; 16];
+    let continued = [0xFFu8; 16];
+    let reader = || BitReader::new(&zeros[..], BitOrder::MsbFirst);
+    assert_eq!(reader().read_gamma().unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(reader().read_delta().unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(reader().read_exp_golomb(3).unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(reader().read_rice(60).unwrap_err().kind(), ErrorKind::InvalidData);
+    let mut reader = BitReader::new(&continued[..], BitOrder::MsbFirst);
+    assert_eq!(reader.read_leb128().unwrap_err().kind(), ErrorKind::InvalidData);
+
+    // A tenth LEB128 byte may only hold the top bit
+    let overlong = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02];
+    let mut reader = BitReader::new(&overlong[..], BitOrder::MsbFirst);
+    assert_eq!(reader.read_leb128().unwrap_err().kind(), ErrorKind::InvalidData);
+    let mut reader = BitReader::new(&overlong[..], BitOrder::MsbFirst);
+    assert_eq!(reader.read_sleb128().unwrap_err().kind(), ErrorKind::InvalidData);
+
+    // A code cut short is an end of file
+    let mut reader = BitReader::new(&[0x01u8][..], BitOrder::MsbFirst);
+    assert_eq!(reader.read_gamma().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+}
+
+proptest! {
+    #[test]
+    fn mixed_codes_round_trip(values: Vec<(u64, i64)>, k in 0u64..=64, lsb_first: bool) {
+        let order = if lsb_first { BitOrder::LsbFirst } else { BitOrder::MsbFirst };
+        let mut writer = BitWriter::new(Vec::new(), order);
+        for &(value, signed) in &values {
+            writer.write_exp_golomb(k, value).unwrap();
+            writer.write_rice(k.max(52), value).unwrap();
+            writer.write_delta(value.max(1)).unwrap();
+            writer.write_exp_golomb(0, zigzag(signed)).unwrap();
+            writer.write_sleb128(signed).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = BitReader::new(&bytes[..], order);
+        for &(value, signed) in &values {
+            prop_assert_eq!(reader.read_exp_golomb(k).unwrap(), value);
+            prop_assert_eq!(reader.read_rice(k.max(52)).unwrap(), value);
+            prop_assert_eq!(reader.read_delta().unwrap(), value.max(1));
+            prop_assert_eq!(unzigzag(reader.read_exp_golomb(0).unwrap()), signed);
+            prop_assert_eq!(reader.read_sleb128().unwrap(), signed);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use bitpack::bitpack::BitpackError;
+use bitpack::fixed::{FixedFormat, Overflow, Rounding};
+use proptest::prelude::*;
+
+/// Q4.4: unsigned, steps of 1/16 up to 15.9375
+const UQ4_4: FixedFormat = FixedFormat::unsigned(4, 4);
+
+/// Q1.7 in two's complement: steps of 1/128 across [-1, 1)
+const Q1_7: FixedFormat = FixedFormat::signed(1, 7);
+
+#[test]
+fn formats_know_their_range() {
+    assert_eq!(UQ4_4.width(), 8);
+    assert_eq!((UQ4_4.min(), UQ4_4.max(), UQ4_4.step()), (0.0, 15.9375, 0.0625));
+    assert_eq!((Q1_7.min(), Q1_7.max(), Q1_7.step()), (-1.0, 127.0 / 128.0, 1.0 / 128.0));
+
+    // Negative steps are stored in two's complement
+    assert_eq!(Q1_7.to_bits(-1.0, Rounding::Nearest, Overflow::Fail), Ok(0x80));
+    assert_eq!(Q1_7.to_bits(-1.0 / 128.0, Rounding::Nearest, Overflow::Fail), Ok(0xFF));
+    assert_eq!(Q1_7.from_bits(0xFF), -1.0 / 128.0);
+    assert_eq!(UQ4_4.to_bits(2.5, Rounding::Nearest, Overflow::Fail), Ok(0x28));
+}
+
+#[test]
+fn rounding_modes_pick_the_expected_step() {
+    let bits = |value, rounding| UQ4_4.to_bits(value, rounding, Overflow::Fail).unwrap();
+
+    // 1/32 and 3/32 lie halfway between steps of 1/16
+    assert_eq!(bits(1.0 / 32.0, Rounding::Nearest), 1);
+    assert_eq!(bits(1.0 / 32.0, Rounding::NearestEven), 0);
+    assert_eq!(bits(3.0 / 32.0, Rounding::NearestEven), 2);
+    assert_eq!(bits(0.1, Rounding::Floor), 1);
+    assert_eq!(bits(0.1, Rounding::Ceil), 2);
+    assert_eq!(bits(0.1, Rounding::TowardZero), 1);
+
+    // Toward zero and floor differ only below zero
+    let bits = |value, rounding| Q1_7.from_bits(Q1_7.to_bits(value, rounding, Overflow::Fail).unwrap());
+    assert_eq!(bits(-0.005, Rounding::Floor), -1.0 / 128.0);
+    assert_eq!(bits(-0.005, Rounding::TowardZero), 0.0);
+    assert_eq!(bits(-0.005, Rounding::Ceil), 0.0);
+    assert_eq!(bits(-3.0 / 256.0, Rounding::Nearest), -2.0 / 128.0);
+}
+
+#[test]
+fn out_of_range_values_saturate_or_fail() {
+    let fail = Err(BitpackError::ValueDoesNotFit { width: 8 });
+    assert_eq!(Q1_7.to_bits(1.0, Rounding::Nearest, Overflow::Fail), fail);
+    assert_eq!(Q1_7.to_bits(1.0, Rounding::Nearest, Overflow::Saturate), Ok(0x7F));
+    assert_eq!(Q1_7.to_bits(-2.0, Rounding::Nearest, Overflow::Saturate), Ok(0x80));
+    assert_eq!(UQ4_4.to_bits(-0.5, Rounding::Nearest, Overflow::Fail), fail);
+    assert_eq!(UQ4_4.to_bits(-0.5, Rounding::Nearest, Overflow::Saturate), Ok(0));
+    assert_eq!(UQ4_4.to_bits(f64::INFINITY, Rounding::Nearest, Overflow::Saturate), Ok(0xFF));
+    assert_eq!(UQ4_4.to_bits(f64::NAN, Rounding::Nearest, Overflow::Saturate), fail);
+
+    // A value that rounds into range fits, one that rounds out of it does not
+    assert_eq!(UQ4_4.to_bits(15.96, Rounding::Floor, Overflow::Fail), Ok(0xFF));
+    assert_eq!(UQ4_4.to_bits(15.96, Rounding::Ceil, Overflow::Fail), fail);
+}
+
+#[test]
+fn fields_hold_fixed_point_values() {
+    let word = UQ4_4.new_field(0, 8, 2.5, Rounding::Nearest, Overflow::Fail).unwrap();
+    let word = Q1_7.new_field(word, 0, -0.5, Rounding::Nearest, Overflow::Fail).unwrap();
+    assert_eq!(word, 0x28C0);
+    assert_eq!(UQ4_4.get_field(word, 8), Ok(2.5));
+    assert_eq!(Q1_7.get_field(word, 0), Ok(-0.5));
+    assert_eq!(Q1_7.get_field(word, 60), Err(BitpackError::FieldOutOfWord { width: 8, lsb: 60 }));
+
+    // The widest formats use the whole word
+    let wide = FixedFormat::signed(64, 0);
+    assert_eq!(wide.to_bits(-1.0, Rounding::Nearest, Overflow::Fail), Ok(u64::MAX));
+    assert_eq!(wide.from_bits(u64::MAX), -1.0);
+}
+
+#[test]
+fn every_value_of_a_small_format_round_trips() {
+    for format in [UQ4_4, Q1_7, FixedFormat::signed(3, 5), FixedFormat::unsigned(0, 8)] {
+        for bits in 0..1 << format.width() {
+            let value = format.from_bits(bits);
+            for rounding in [Rounding::Nearest, Rounding::NearestEven, Rounding::Floor, Rounding::Ceil] {
+                assert_eq!(format.to_bits(value, rounding, Overflow::Fail), Ok(bits), "{format:?} {value}");
+            }
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn rounding_moves_at_most_a_step(value in -7.5f64..7.5, frac_bits in 0u64..40) {
+        let format = FixedFormat::signed(4, frac_bits);
+        let nearest = format.from_bits(format.to_bits(value, Rounding::Nearest, Overflow::Fail).unwrap());
+        let floor = format.from_bits(format.to_bits(value, Rounding::Floor, Overflow::Fail).unwrap());
+        prop_assert!((nearest - value).abs() <= format.step() / 2.0);
+        prop_assert!(floor <= value && value - floor < format.step());
+    }
+}
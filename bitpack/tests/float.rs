@@ -0,0 +1,103 @@
+use bitpack::float::FloatFormat;
+use proptest::prelude::*;
+
+/// An 8-bit float with 4 exponent and 3 mantissa bits
+const E4M3: FloatFormat = FloatFormat::new(4, 3);
+
+#[test]
+fn half_precision_matches_known_codes() {
+    let half = FloatFormat::HALF;
+    assert_eq!(half.width(), 16);
+    assert_eq!(half.to_bits(1.0), 0x3C00);
+    assert_eq!(half.to_bits(-2.0), 0xC000);
+    assert_eq!(half.to_bits(65504.0), 0x7BFF);
+    assert_eq!(half.to_bits(0.1), 0x2E66);
+    assert_eq!(half.to_bits(-0.0), 0x8000);
+
+    // The smallest subnormal and normal
+    assert_eq!(half.to_bits((-24.0f64).exp2()), 0x0001);
+    assert_eq!(half.to_bits((-14.0f64).exp2()), 0x0400);
+    assert_eq!(half.from_bits(0x0001), (-24.0f64).exp2());
+    assert_eq!(half.from_bits(0x03FF), 1023.0 * (-24.0f64).exp2());
+}
+
+#[test]
+fn bfloat16_keeps_the_top_of_an_f32() {
+    let bf16 = FloatFormat::BFLOAT16;
+    assert_eq!(bf16.to_bits(1.0), 0x3F80);
+    assert_eq!(bf16.to_bits(-3.0), 0xC040);
+    assert_eq!(bf16.to_bits(f32::MAX as f64), 0x7F80);
+    assert_eq!(bf16.from_bits(0x4049), 3.140625);
+}
+
+#[test]
+fn rounding_is_to_nearest_with_ties_to_even() {
+    // 1 + 1/16 and 1 + 3/16 lie halfway between steps of 1/8
+    assert_eq!(E4M3.from_bits(E4M3.to_bits(1.0625)), 1.0);
+    assert_eq!(E4M3.from_bits(E4M3.to_bits(1.1875)), 1.25);
+    assert_eq!(E4M3.from_bits(E4M3.to_bits(1.07)), 1.125);
+
+    // Rounding up the largest mantissa carries into the exponent
+    assert_eq!(E4M3.from_bits(E4M3.to_bits(1.97)), 2.0);
+
+    // Past the largest finite value the result is infinite, below the
+    // smallest subnormal it is zero
+    assert_eq!(E4M3.from_bits(E4M3.to_bits(240.0)), 240.0);
+    assert_eq!(E4M3.from_bits(E4M3.to_bits(248.0)), f64::INFINITY);
+    assert_eq!(E4M3.from_bits(E4M3.to_bits(-1e-9)), -0.0);
+    assert!(E4M3.to_bits(-1e-9) & 0x80 != 0);
+}
+
+#[test]
+fn special_values_have_their_codes() {
+    let half = FloatFormat::HALF;
+    assert_eq!(half.to_bits(f64::INFINITY), 0x7C00);
+    assert_eq!(half.to_bits(f64::NEG_INFINITY), 0xFC00);
+    assert_eq!(half.to_bits(1e10), 0x7C00);
+    assert_eq!(half.to_bits(f64::NAN), 0x7E00);
+    assert!(half.from_bits(0x7C01).is_nan());
+    assert_eq!(half.from_bits(0xFC00), f64::NEG_INFINITY);
+}
+
+#[test]
+fn every_code_of_a_small_format_round_trips() {
+    for format in [FloatFormat::HALF, FloatFormat::BFLOAT16, E4M3, FloatFormat::new(2, 1)] {
+        for bits in 0..1 << format.width() {
+            let value = format.from_bits(bits);
+            if !value.is_nan() {
+                assert_eq!(format.to_bits(value), bits, "{format:?} {value}");
+            }
+        }
+    }
+}
+
+#[test]
+fn fields_hold_floats() {
+    let word = FloatFormat::HALF.new_field(0, 32, 1.5).unwrap();
+    let word = E4M3.new_field(word, 0, -0.5).unwrap();
+    assert_eq!(FloatFormat::HALF.get_field(word, 32), Ok(1.5));
+    assert_eq!(E4M3.get_field(word, 0), Ok(-0.5));
+    assert!(FloatFormat::HALF.get_field(word, 50).is_err());
+}
+
+proptest! {
+    #[test]
+    fn single_precision_rounds_like_the_standard_library(bits: u64) {
+        let value = f64::from_bits(bits);
+        let expected = value as f32;
+        let actual = FloatFormat::SINGLE.to_bits(value) as u32;
+        if expected.is_nan() {
+            prop_assert!(f32::from_bits(actual).is_nan());
+        } else {
+            prop_assert_eq!(actual, expected.to_bits());
+            prop_assert_eq!(FloatFormat::SINGLE.from_bits(actual as u64), expected as f64);
+        }
+    }
+
+    #[test]
+    fn f32_values_survive_single_precision(bits: u32) {
+        let value = f32::from_bits(bits);
+        prop_assume!(!value.is_nan());
+        prop_assert_eq!(FloatFormat::SINGLE.to_bits(value as f64), bits as u64);
+    }
+}
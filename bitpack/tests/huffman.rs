@@ -0,0 +1,181 @@
+use std::io::ErrorKind;
+
+use bitpack::codes::zigzag;
+use bitpack::huffman::{HuffmanCode, HuffmanDecoder, HuffmanError, MAX_CODE_LEN};
+use bitpack::stream::{BitOrder, BitReader, BitWriter};
+use proptest::prelude::*;
+
+/// Frequencies following the Fibonacci numbers, whose unlimited Huffman code is as deep as possible
+fn fibonacci(count: usize) -> Vec<u64> {
+    let mut frequencies = vec![1, 1];
+    while frequencies.len() < count {
+        frequencies.push(frequencies[frequencies.len() - 1] + frequencies[frequencies.len() - 2]);
+    }
+    frequencies
+}
+
+/// The total bits of a message with `frequencies` coded by `code`
+fn total_bits(code: &HuffmanCode, frequencies: &[u64]) -> u64 {
+    code.lengths().iter().zip(frequencies).map(|(&len, &frequency)| len as u64 * frequency).sum()
+}
+
+/// Writes the table and `symbols`, then reads both back
+fn round_trip(code: &HuffmanCode, symbols: &[usize], order: BitOrder) -> Vec<usize> {
+    let mut writer = BitWriter::new(Vec::new(), order);
+    code.write_table(&mut writer).unwrap();
+    for &symbol in symbols {
+        code.encode(&mut writer, symbol).unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+
+    let mut reader = BitReader::new(&bytes[..], order);
+    let read = HuffmanCode::read_table(&mut reader).unwrap();
+    assert_eq!(&read, code);
+    let decoder = HuffmanDecoder::new(&read);
+    symbols.iter().map(|_| decoder.decode(&mut reader).unwrap()).collect()
+}
+
+#[test]
+fn codes_are_canonical() {
+    // The textbook example: A gets 1 bit, B 2 and C and D 3
+    let code = HuffmanCode::from_frequencies(&[8, 4, 2, 2], 15).unwrap();
+    assert_eq!(code.lengths(), &[1, 2, 3, 3]);
+    assert_eq!(code.code(0), Some((1, 0b0)));
+    assert_eq!(code.code(1), Some((2, 0b10)));
+    assert_eq!(code.code(2), Some((3, 0b110)));
+    assert_eq!(code.code(3), Some((3, 0b111)));
+
+    // Codes of the same length are in symbol order whatever the frequencies
+    let code = HuffmanCode::from_lengths(&[2, 0, 2, 1]).unwrap();
+    assert_eq!(code.code(3), Some((1, 0b0)));
+    assert_eq!(code.code(0), Some((2, 0b10)));
+    assert_eq!(code.code(2), Some((2, 0b11)));
+    assert_eq!(code.code(1), None);
+}
+
+#[test]
+fn lengths_are_limited_at_the_least_cost() {
+    let frequencies = fibonacci(20);
+    let unlimited = HuffmanCode::from_frequencies(&frequencies, MAX_CODE_LEN).unwrap();
+    assert_eq!(unlimited.max_len(), 19);
+
+    let limited = HuffmanCode::from_frequencies(&frequencies, 7).unwrap();
+    assert_eq!(limited.max_len(), 7);
+    assert!(total_bits(&limited, &frequencies) > total_bits(&unlimited, &frequencies));
+
+    // The limit can be as tight as the symbols allow, which is a fixed-length code
+    let flat = HuffmanCode::from_frequencies(&frequencies[..16], 4).unwrap();
+    assert!(flat.lengths().iter().all(|&len| len == 4));
+    assert_eq!(
+        HuffmanCode::from_frequencies(&frequencies, 4),
+        Err(HuffmanError::TooManySymbols { symbols: 20, max_len: 4 })
+    );
+}
+
+#[test]
+fn unused_and_lone_symbols_are_handled() {
+    let code = HuffmanCode::from_frequencies(&[0, 0, 0], 8).unwrap();
+    assert_eq!(code.lengths(), &[0, 0, 0]);
+    assert_eq!(round_trip(&code, &[], BitOrder::MsbFirst), Vec::<usize>::new());
+
+    let code = HuffmanCode::from_frequencies(&[0, 5, 0], 8).unwrap();
+    assert_eq!(code.lengths(), &[0, 1, 0]);
+    assert_eq!(round_trip(&code, &[1, 1, 1], BitOrder::LsbFirst), vec![1, 1, 1]);
+}
+
+#[test]
+fn bad_codes_and_streams_are_errors() {
+    assert_eq!(HuffmanCode::from_lengths(&[1, 1, 1]), Err(HuffmanError::Oversubscribed));
+    assert_eq!(HuffmanCode::from_lengths(&[33]), Err(HuffmanError::CodeTooLong { len: 33 }));
+    assert_eq!(HuffmanCode::from_frequencies(&[1], 33), Err(HuffmanError::CodeTooLong { len: 33 }));
+
+    let code = HuffmanCode::from_lengths(&[1, 2]).unwrap();
+    let mut writer = BitWriter::new(Vec::new(), BitOrder::MsbFirst);
+    assert_eq!(code.encode(&mut writer, 2).unwrap_err().kind(), ErrorKind::InvalidInput);
+
+    // 11 starts no code of an incomplete code, and a code cut short is the end of the stream
+    let decoder = HuffmanDecoder::new(&code);
+    let mut reader = BitReader::new(&[0b1100_0000][..], BitOrder::MsbFirst);
+    assert_eq!(decoder.decode(&mut reader).unwrap_err().kind(), ErrorKind::InvalidData);
+    let mut reader = BitReader::new(&[0b0000_0000][..], BitOrder::MsbFirst);
+    for _ in 0..8 {
+        assert_eq!(decoder.decode(&mut reader).unwrap(), 0);
+    }
+    assert_eq!(decoder.decode(&mut reader).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+    // A table whose lengths run past its symbols
+    let mut writer = BitWriter::new(Vec::new(), BitOrder::MsbFirst);
+    writer.write_exp_golomb(0, 2).unwrap();
+    writer.write_exp_golomb(0, zigzag(1)).unwrap();
+    writer.write_exp_golomb(0, 2).unwrap();
+    let bytes = writer.finish().unwrap();
+    let error = HuffmanCode::read_table(&mut BitReader::new(&bytes[..], BitOrder::MsbFirst)).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn tables_are_compact() {
+    // 256 symbols where only a few dozen are used take a handful of bytes
+    let mut frequencies = vec![0; 256];
+    for (symbol, frequency) in frequencies.iter_mut().enumerate().take(40) {
+        *frequency = 100 - symbol as u64;
+    }
+    let code = HuffmanCode::from_frequencies(&frequencies, 15).unwrap();
+    let mut writer = BitWriter::new(Vec::new(), BitOrder::MsbFirst);
+    code.write_table(&mut writer).unwrap();
+    assert!(writer.finish().unwrap().len() < 12);
+}
+
+#[test]
+fn long_codes_decode_past_the_lookup_table() {
+    let frequencies = fibonacci(30);
+    let code = HuffmanCode::from_frequencies(&frequencies, 24).unwrap();
+    assert_eq!(code.max_len(), 24);
+    let symbols: Vec<usize> = (0..30).chain((0..30).rev()).collect();
+    for order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+        assert_eq!(round_trip(&code, &symbols, order), symbols);
+    }
+}
+
+#[test]
+fn peeking_does_not_consume_bits() {
+    let bytes = [0b1010_1100, 0b1111_0000];
+    let mut reader = BitReader::new(&bytes[..], BitOrder::MsbFirst);
+    assert_eq!(reader.readu(3).unwrap(), 0b101);
+    assert_eq!(reader.peeku(10).unwrap(), 0b01100_11110);
+    assert_eq!(reader.readu(10).unwrap(), 0b01100_11110);
+
+    // Bits past the end read as zeros
+    assert_eq!(reader.peeku(8).unwrap(), 0b0000_0000);
+    assert_eq!(reader.readu(3).unwrap(), 0);
+
+    let mut reader = BitReader::new(&bytes[..], BitOrder::LsbFirst);
+    assert_eq!(reader.readu(2).unwrap(), 0b00);
+    assert_eq!(reader.peeku(12).unwrap(), 0b1100_0010_1011);
+    assert_eq!(reader.readu(12).unwrap(), 0b1100_0010_1011);
+    assert_eq!(reader.peeku(4).unwrap(), 0b0011);
+}
+
+proptest! {
+    #[test]
+    fn codes_match_unlimited_huffman_and_round_trip(
+        symbols in prop::collection::vec(0usize..64, 1..400),
+        lsb_first: bool,
+    ) {
+        let mut frequencies = vec![0u64; 64];
+        for &symbol in &symbols {
+            frequencies[symbol] += 1;
+        }
+        let code = HuffmanCode::from_frequencies(&frequencies, MAX_CODE_LEN).unwrap();
+
+        // Kraft's equality holds for any complete code of two or more symbols
+        let used = frequencies.iter().filter(|&&frequency| frequency > 0).count();
+        if used > 1 {
+            let kraft: f64 = code.lengths().iter().filter(|&&len| len > 0).map(|&len| (-(len as f64)).exp2()).sum();
+            prop_assert_eq!(kraft, 1.0);
+        }
+
+        let order = if lsb_first { BitOrder::LsbFirst } else { BitOrder::MsbFirst };
+        prop_assert_eq!(round_trip(&code, &symbols, order), symbols);
+    }
+}
@@ -0,0 +1,163 @@
+use std::io::ErrorKind;
+
+use bitpack::range::{magnitude_context, BitModel, BitTree, IntModel, RangeDecoder, RangeEncoder};
+use proptest::prelude::*;
+
+/// Bits where one in `period` is set, from a simple LCG
+fn skewed_bits(count: usize, period: u64) -> Vec<bool> {
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    (0..count)
+        .map(|_| {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            (state >> 33).is_multiple_of(period)
+        })
+        .collect()
+}
+
+#[test]
+fn skewed_bits_compress_toward_their_entropy() {
+    let bits = skewed_bits(10_000, 20);
+    let mut encoder = RangeEncoder::new(Vec::new());
+    let mut model = BitModel::new();
+    for &bit in &bits {
+        encoder.encode_bit(&mut model, bit).unwrap();
+    }
+    let bytes = encoder.finish().unwrap();
+
+    // One bit in 20 carries about 0.29 bits of information, or 360 bytes here
+    assert!(bytes.len() < 450, "{} bytes", bytes.len());
+    assert!(model.probability() > 0.85);
+
+    let mut decoder = RangeDecoder::new(&bytes[..]).unwrap();
+    let mut model = BitModel::new();
+    for &bit in &bits {
+        assert_eq!(decoder.decode_bit(&mut model).unwrap(), bit);
+    }
+}
+
+#[test]
+fn models_adapt_at_their_rate() {
+    let (mut fast, mut slow) = (BitModel::with_shift(2), BitModel::with_shift(8));
+    assert_eq!(fast.probability(), 0.5);
+    let mut encoder = RangeEncoder::new(Vec::new());
+    for _ in 0..8 {
+        encoder.encode_bit(&mut fast, true).unwrap();
+        encoder.encode_bit(&mut slow, true).unwrap();
+    }
+    assert!(fast.probability() < 0.1);
+    assert!(slow.probability() > 0.45);
+
+    // Even a model sure of the other bit can still code this one
+    for _ in 0..100 {
+        encoder.encode_bit(&mut fast, true).unwrap();
+    }
+    encoder.encode_bit(&mut fast, false).unwrap();
+    let bytes = encoder.finish().unwrap();
+
+    let mut decoder = RangeDecoder::new(&bytes[..]).unwrap();
+    let (mut fast, mut slow) = (BitModel::with_shift(2), BitModel::with_shift(8));
+    for _ in 0..8 {
+        assert!(decoder.decode_bit(&mut fast).unwrap());
+        assert!(decoder.decode_bit(&mut slow).unwrap());
+    }
+    for _ in 0..100 {
+        assert!(decoder.decode_bit(&mut fast).unwrap());
+    }
+    assert!(!decoder.decode_bit(&mut fast).unwrap());
+}
+
+#[test]
+fn integers_trees_and_direct_bits_round_trip() {
+    let values = [0, 1, -1, 2, -2, 1000, -1000, i64::MAX, i64::MIN, i64::MIN + 1];
+    let mut encoder = RangeEncoder::new(Vec::new());
+    let (mut ints, mut tree) = (IntModel::new(), BitTree::new(5));
+    for (index, &value) in values.iter().enumerate() {
+        encoder.encode_int(&mut ints, value).unwrap();
+        encoder.encode_tree(&mut tree, index as u64 * 3).unwrap();
+        encoder.encode_direct(64, value as u64).unwrap();
+    }
+    let bytes = encoder.finish().unwrap();
+
+    let mut decoder = RangeDecoder::new(&bytes[..]).unwrap();
+    let (mut ints, mut tree) = (IntModel::new(), BitTree::new(5));
+    for (index, &value) in values.iter().enumerate() {
+        assert_eq!(decoder.decode_int(&mut ints).unwrap(), value);
+        assert_eq!(decoder.decode_tree(&mut tree).unwrap(), index as u64 * 3);
+        assert_eq!(decoder.decode_direct(64).unwrap(), value as u64);
+    }
+}
+
+#[test]
+fn contexts_separate_differently_distributed_values() {
+    // Values next to large neighbours are large, so a model per context learns each kind
+    let neighbours: Vec<i64> = (0..2000).map(|i| if i % 7 < 3 { 0 } else { 40 + i % 9 }).collect();
+    let values: Vec<i64> = neighbours.iter().map(|&n| if n == 0 { 0 } else { n - 44 }).collect();
+    let code = |contexts: usize| {
+        let mut models = vec![IntModel::new(); contexts];
+        let mut encoder = RangeEncoder::new(Vec::new());
+        for (&value, &neighbour) in values.iter().zip(&neighbours) {
+            let context = magnitude_context(&[neighbour], contexts);
+            encoder.encode_int(&mut models[context], value).unwrap();
+        }
+        encoder.finish().unwrap()
+    };
+    let (shared, separate) = (code(1), code(8));
+    assert!(separate.len() < shared.len(), "{} >= {}", separate.len(), shared.len());
+
+    let mut models = vec![IntModel::new(); 8];
+    let mut decoder = RangeDecoder::new(&separate[..]).unwrap();
+    for (&value, &neighbour) in values.iter().zip(&neighbours) {
+        let context = magnitude_context(&[neighbour], 8);
+        assert_eq!(decoder.decode_int(&mut models[context]).unwrap(), value);
+    }
+}
+
+#[test]
+fn magnitude_contexts_grow_with_their_neighbours() {
+    assert_eq!(magnitude_context(&[], 4), 0);
+    assert_eq!(magnitude_context(&[0, 0], 4), 0);
+    assert_eq!(magnitude_context(&[1, 0], 4), 1);
+    assert_eq!(magnitude_context(&[-2, 1], 4), 2);
+    assert_eq!(magnitude_context(&[100, -100], 4), 3);
+    assert_eq!(magnitude_context(&[i64::MIN, i64::MIN], 100), 64);
+    assert_eq!(magnitude_context(&[5], 0), 0);
+}
+
+#[test]
+fn bad_values_and_streams_are_errors() {
+    let mut encoder = RangeEncoder::new(Vec::new());
+    assert_eq!(encoder.encode_direct(3, 8).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(encoder.encode_direct(65, 0).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(encoder.encode_tree(&mut BitTree::new(4), 16).unwrap_err().kind(), ErrorKind::InvalidInput);
+
+    assert_eq!(RangeDecoder::new(&[0u8, 1][..]).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    let bytes = RangeEncoder::new(Vec::new()).finish().unwrap();
+    let mut decoder = RangeDecoder::new(&bytes[..]).unwrap();
+    assert_eq!(decoder.decode_direct(64).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+}
+
+proptest! {
+    #[test]
+    fn mixed_symbols_round_trip(symbols: Vec<(bool, u8, i64, u16)>, contexts in 1usize..4) {
+        let mut encoder = RangeEncoder::new(Vec::new());
+        let mut bits = vec![BitModel::new(); contexts];
+        let (mut ints, mut tree) = (IntModel::new(), BitTree::new(8));
+        for &(bit, byte, int, raw) in &symbols {
+            encoder.encode_bit(&mut bits[byte as usize % contexts], bit).unwrap();
+            encoder.encode_tree(&mut tree, byte as u64).unwrap();
+            encoder.encode_int(&mut ints, int).unwrap();
+            encoder.encode_direct(16, raw as u64).unwrap();
+        }
+        let bytes = encoder.finish().unwrap();
+
+        let mut decoder = RangeDecoder::new(&bytes[..]).unwrap();
+        let mut bits = vec![BitModel::new(); contexts];
+        let (mut ints, mut tree) = (IntModel::new(), BitTree::new(8));
+        for &(bit, byte, int, raw) in &symbols {
+            prop_assert_eq!(decoder.decode_bit(&mut bits[byte as usize % contexts]).unwrap(), bit);
+            prop_assert_eq!(decoder.decode_tree(&mut tree).unwrap(), byte as u64);
+            prop_assert_eq!(decoder.decode_int(&mut ints).unwrap(), int);
+            prop_assert_eq!(decoder.decode_direct(16).unwrap(), raw as u64);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use bitpack::bitpack::BitpackError;
+use bitpack::serial::{byte_len, words_from_bytes, words_to_bytes, ByteOrder};
+use proptest::prelude::*;
+
+#[test]
+fn whole_byte_words_follow_the_byte_order() {
+    let words = [0x12_3456u32, 0xAB_CDEF];
+    assert_eq!(words_to_bytes(&words, 24, ByteOrder::BigEndian), Ok(vec![0x12, 0x34, 0x56, 0xAB, 0xCD, 0xEF]));
+    assert_eq!(words_to_bytes(&words, 24, ByteOrder::LittleEndian), Ok(vec![0x56, 0x34, 0x12, 0xEF, 0xCD, 0xAB]));
+
+    let word = [0x01_0203_0405u64];
+    assert_eq!(words_to_bytes(&word, 40, ByteOrder::BigEndian), Ok(vec![1, 2, 3, 4, 5]));
+    assert_eq!(words_to_bytes(&word, 40, ByteOrder::LittleEndian), Ok(vec![5, 4, 3, 2, 1]));
+
+    // Matches the standard library for native widths
+    let word = 0x0102_0304_0506_0708_090A_0B0C_0D0E_0F10u128;
+    assert_eq!(words_to_bytes(&[word], 128, ByteOrder::BigEndian), Ok(word.to_be_bytes().to_vec()));
+    assert_eq!(words_to_bytes(&[word], 128, ByteOrder::LittleEndian), Ok(word.to_le_bytes().to_vec()));
+    assert_eq!(words_from_bytes(&word.to_le_bytes(), 128, ByteOrder::LittleEndian), Ok(vec![word]));
+}
+
+#[test]
+fn narrow_words_are_packed_without_padding() {
+    let words = [0b101u8, 0b011, 0b110];
+    assert_eq!(words_to_bytes(&words, 3, ByteOrder::BigEndian), Ok(vec![0b1010_1111, 0b0000_0000]));
+    assert_eq!(words_to_bytes(&words, 3, ByteOrder::LittleEndian), Ok(vec![0b1001_1101, 0b0000_0001]));
+    assert_eq!(byte_len(3, 3), 2);
+    assert_eq!(byte_len(1000, 30), 3750);
+
+    // Four 30-bit words fill 15 bytes where 32-bit words would take 16
+    let words = [0x3FFF_FFFFu32, 0, 0x2AAA_AAAA, 1];
+    let bytes = words_to_bytes(&words, 30, ByteOrder::BigEndian).unwrap();
+    assert_eq!(bytes.len(), 15);
+    assert_eq!(words_from_bytes(&bytes, 30, ByteOrder::BigEndian), Ok(words.to_vec()));
+}
+
+#[test]
+fn bad_widths_and_words_are_rejected() {
+    assert_eq!(words_to_bytes(&[0u32], 33, ByteOrder::BigEndian), Err(BitpackError::WidthTooLarge { width: 33 }));
+    assert_eq!(words_from_bytes::<u16>(&[0; 4], 17, ByteOrder::BigEndian), Err(BitpackError::WidthTooLarge { width: 17 }));
+    assert_eq!(words_to_bytes(&[1u64 << 40], 40, ByteOrder::BigEndian), Err(BitpackError::ValueDoesNotFit { width: 40 }));
+    assert_eq!(words_to_bytes(&[0u8; 4], 0, ByteOrder::BigEndian), Ok(vec![]));
+    assert_eq!(words_from_bytes::<u8>(&[1, 2], 0, ByteOrder::BigEndian), Ok(vec![]));
+
+    // A partial word at the end is not read
+    assert_eq!(words_from_bytes::<u32>(&[1, 2, 3, 4, 5], 24, ByteOrder::BigEndian), Ok(vec![0x01_0203]));
+}
+
+proptest! {
+    #[test]
+    fn words_round_trip(words: Vec<u128>, bits in 1u64..=128, little: bool) {
+        let order = if little { ByteOrder::LittleEndian } else { ByteOrder::BigEndian };
+        let mask = u128::MAX >> (128 - bits);
+        let words: Vec<u128> = words.into_iter().map(|word| word & mask).collect();
+        let bytes = words_to_bytes(&words, bits, order).unwrap();
+        prop_assert_eq!(bytes.len(), byte_len(words.len(), bits));
+
+        // Padding of a byte or more reads as extra zero words, which are dropped here
+        let mut read: Vec<u128> = words_from_bytes(&bytes, bits, order).unwrap();
+        prop_assert!(read[words.len()..].iter().all(|&word| word == 0));
+        read.truncate(words.len());
+        prop_assert_eq!(read, words);
+    }
+}
@@ -0,0 +1,208 @@
+use std::io::{ErrorKind, Read, Write};
+
+use bitpack::stream::{BitOrder, BitReader, BitWriter};
+use proptest::prelude::*;
+
+const ORDERS: [BitOrder; 2] = [BitOrder::MsbFirst, BitOrder::LsbFirst];
+
+/// Writes unsigned fields of the given widths and returns the bytes
+fn write_fields(order: BitOrder, fields: &[(u64, u64)]) -> Vec<u8> {
+    let mut writer = BitWriter::new(Vec::new(), order);
+    for &(width, value) in fields {
+        writer.writeu(width, value).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+#[test]
+fn fields_cross_byte_boundaries_in_both_orders() {
+    let fields = [(3, 0b101), (7, 0b110_0110), (6, 0b01_1011)];
+
+    // 101 1100110 011011, filling each byte from the top
+    assert_eq!(write_fields(BitOrder::MsbFirst, &fields), [0b1011_1001, 0b1001_1011]);
+    // The same fields filling each byte from the bottom, low bits first
+    assert_eq!(write_fields(BitOrder::LsbFirst, &fields), [0b0011_0101, 0b0110_1111]);
+
+    for order in ORDERS {
+        let bytes = write_fields(order, &fields);
+        let mut reader = BitReader::new(&bytes[..], order);
+        for (width, value) in fields {
+            assert_eq!(reader.readu(width).unwrap(), value, "{:?}", order);
+        }
+    }
+}
+
+#[test]
+fn widths_of_zero_and_sixty_four() {
+    let fields = [(0, 0), (64, u64::MAX), (1, 1), (64, 0x0123_4567_89AB_CDEF), (0, 0), (7, 0x55)];
+    for order in ORDERS {
+        let bytes = write_fields(order, &fields);
+        assert_eq!(bytes.len(), 17, "{:?}", order);
+
+        let mut reader = BitReader::new(&bytes[..], order);
+        for (width, value) in fields {
+            assert_eq!(reader.readu(width).unwrap(), value, "{:?}", order);
+        }
+
+        let mut writer = BitWriter::new(Vec::new(), order);
+        writer.writes(64, i64::MIN).unwrap();
+        writer.writes(0, 0).unwrap();
+        writer.writes(64, i64::MAX).unwrap();
+        let bytes = writer.finish().unwrap();
+        let mut reader = BitReader::new(&bytes[..], order);
+        assert_eq!(reader.reads(64).unwrap(), i64::MIN);
+        assert_eq!(reader.reads(0).unwrap(), 0);
+        assert_eq!(reader.reads(64).unwrap(), i64::MAX);
+    }
+
+    // A zero-width field holds nothing, and no field is wider than 64 bits
+    let mut writer = BitWriter::new(Vec::new(), BitOrder::MsbFirst);
+    assert_eq!(writer.writeu(0, 1).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(writer.writeu(65, 0).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(writer.finish().unwrap(), []);
+
+    let mut reader = BitReader::new(&[][..], BitOrder::MsbFirst);
+    assert_eq!(reader.readu(0).unwrap(), 0);
+    assert_eq!(reader.readu(65).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(reader.peeku(65).unwrap_err().kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn signed_fields_reject_values_that_do_not_fit() {
+    let mut writer = BitWriter::new(Vec::new(), BitOrder::MsbFirst);
+    writer.writes(4, -8).unwrap();
+    writer.writes(4, 7).unwrap();
+    assert_eq!(writer.writes(4, 8).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(writer.writes(4, -9).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(writer.finish().unwrap(), [0x87]);
+}
+
+#[test]
+fn end_of_stream_partway_through_a_field() {
+    for order in ORDERS {
+        let mut reader = BitReader::new(&[0xAB, 0xCD][..], order);
+        reader.readu(4).unwrap();
+        assert_eq!(reader.readu(16).unwrap_err().kind(), ErrorKind::UnexpectedEof, "{:?}", order);
+
+        let mut reader = BitReader::new(&[0xAB][..], order);
+        assert_eq!(reader.reads(9).unwrap_err().kind(), ErrorKind::UnexpectedEof, "{:?}", order);
+    }
+}
+
+#[test]
+fn align_pads_and_skips_to_the_next_byte() {
+    for (order, first) in [(BitOrder::MsbFirst, 0b1110_0000), (BitOrder::LsbFirst, 0b0000_0111)] {
+        let mut writer = BitWriter::new(Vec::new(), order);
+        writer.writeu(3, 0b111).unwrap();
+        writer.align().unwrap();
+        writer.writeu(8, 0xAA).unwrap();
+        writer.align().unwrap();
+        let bytes = writer.finish().unwrap();
+        assert_eq!(bytes, [first, 0xAA], "{:?}", order);
+
+        let mut reader = BitReader::new(&bytes[..], order);
+        assert_eq!(reader.readu(3).unwrap(), 0b111);
+        reader.align();
+        assert_eq!(reader.readu(8).unwrap(), 0xAA);
+        reader.align();
+        assert_eq!(reader.readu(1).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+}
+
+#[test]
+fn align_after_peeku_keeps_the_bytes_peeked() {
+    let bytes = [0b1110_0000, 0xAA, 0x55];
+
+    let mut reader = BitReader::new(&bytes[..], BitOrder::MsbFirst);
+    assert_eq!(reader.readu(3).unwrap(), 0b111);
+    // The last 5 bits of the first byte, all of 0xAA and the top 3 bits of 0x55
+    assert_eq!(reader.peeku(16).unwrap(), 0b0000_0101_0101_0010);
+    reader.align();
+    assert_eq!(reader.readu(8).unwrap(), 0xAA);
+    assert_eq!(reader.peeku(12).unwrap(), 0x550);
+    assert_eq!(reader.readu(8).unwrap(), 0x55);
+    assert_eq!(reader.readu(1).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+    let mut reader = BitReader::new(&bytes[..], BitOrder::LsbFirst);
+    reader.readu(3).unwrap();
+    // The same bits taken from the bottom of each byte, the earliest lowest
+    assert_eq!(reader.peeku(16).unwrap(), 0b1011_0101_0101_1100);
+    reader.align();
+    assert_eq!(reader.readu(8).unwrap(), 0xAA);
+    assert_eq!(reader.readu(8).unwrap(), 0x55);
+}
+
+#[test]
+fn bytes_pass_through_the_io_traits_off_a_byte_boundary() {
+    // 1111, then "hi" as 8-bit fields, then 0000
+    let mut writer = BitWriter::new(Vec::new(), BitOrder::MsbFirst);
+    writer.writeu(4, 0xF).unwrap();
+    writer.write_all(b"hi").unwrap();
+    writer.flush().unwrap();
+    writer.writeu(4, 0).unwrap();
+    assert_eq!(writer.finish().unwrap(), [0xF6, 0x86, 0x90]);
+
+    for order in ORDERS {
+        let mut writer = BitWriter::new(Vec::new(), order);
+        writer.writeu(4, 0xA).unwrap();
+        writer.write_all(b"bits").unwrap();
+        writer.writeu(4, 0x5).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = BitReader::new(&bytes[..], order);
+        assert_eq!(reader.readu(4).unwrap(), 0xA);
+        let mut text = [0u8; 4];
+        reader.read_exact(&mut text).unwrap();
+        assert_eq!(&text, b"bits", "{:?}", order);
+        assert_eq!(reader.readu(4).unwrap(), 0x5);
+    }
+}
+
+#[test]
+fn read_drops_a_trailing_partial_byte() {
+    for order in ORDERS {
+        // 4 bits, then 3 bytes, which leaves 4 bits of padding
+        let mut writer = BitWriter::new(Vec::new(), order);
+        writer.writeu(4, 0x3).unwrap();
+        writer.write_all(b"abc").unwrap();
+        let bytes = writer.finish().unwrap();
+        assert_eq!(bytes.len(), 4);
+
+        let mut reader = BitReader::new(&bytes[..], order);
+        reader.readu(4).unwrap();
+        let mut rest = Vec::new();
+        assert_eq!(reader.read_to_end(&mut rest).unwrap(), 3, "{:?}", order);
+        assert_eq!(rest, b"abc");
+
+        // A short read stops at the last whole byte
+        let mut reader = BitReader::new(&bytes[..], order);
+        reader.readu(4).unwrap();
+        let mut buf = [0u8; 8];
+        assert_eq!(reader.read(&mut buf).unwrap(), 3, "{:?}", order);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0, "{:?}", order);
+    }
+}
+
+proptest! {
+    #[test]
+    fn signed_fields_round_trip(fields: Vec<(u64, i64)>, lsb_first: bool) {
+        let order = if lsb_first { BitOrder::LsbFirst } else { BitOrder::MsbFirst };
+
+        // Shift each value down so it fits a width of 1 to 64
+        let fields: Vec<(u64, i64)> = fields.into_iter().map(|(width, value)| {
+            let width = width % 64 + 1;
+            (width, value >> (64 - width))
+        }).collect();
+
+        let mut writer = BitWriter::new(Vec::new(), order);
+        for &(width, value) in &fields {
+            writer.writes(width, value).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = BitReader::new(&bytes[..], order);
+        for &(width, value) in &fields {
+            prop_assert_eq!(reader.reads(width).unwrap(), value);
+        }
+    }
+}
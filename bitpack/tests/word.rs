@@ -0,0 +1,60 @@
+use bitpack::bitpack::{get_signed, get_unsigned, new_signed, new_unsigned, BitpackError};
+use bitpack::word::Word;
+use proptest::prelude::*;
+
+#[test]
+fn fields_are_checked_against_the_word_size() {
+    assert_eq!(u8::check_field(8, 0), Ok(()));
+    assert_eq!(u8::check_field(9, 0), Err(BitpackError::WidthTooLarge { width: 9 }));
+    assert_eq!(u16::check_field(4, 13), Err(BitpackError::FieldOutOfWord { width: 4, lsb: 13 }));
+    assert_eq!(u32::check_field(9, 23), Ok(()));
+    assert_eq!(u32::check_field(12, 36), Err(BitpackError::FieldOutOfWord { width: 12, lsb: 36 }));
+    assert_eq!(u64::check_field(65, 0), Err(BitpackError::WidthTooLarge { width: 65 }));
+    assert_eq!(u128::check_field(128, 0), Ok(()));
+    assert_eq!(u128::check_field(0, 128), Ok(()));
+    assert_eq!(u128::check_field(1, 128), Err(BitpackError::FieldOutOfWord { width: 1, lsb: 128 }));
+}
+
+#[test]
+fn small_words_hold_their_extremes() {
+    assert_eq!(0u8.new_signed(4, 4, -8).and_then(|word| word.get_signed(4, 4)), Ok(-8));
+    assert_eq!(0u8.new_signed(8, 0, i8::MIN), Ok(0x80));
+    assert_eq!(0xFFu8.new_unsigned(3, 5, 0), Ok(0x1F));
+    assert_eq!(0u16.new_signed(5, 11, 16), Err(BitpackError::ValueDoesNotFit { width: 5 }));
+    assert_eq!(0xFFFF_FFFFu32.get_signed(32, 0), Ok(-1));
+    assert_eq!(0u32.new_unsigned(0, 32, 0), Ok(0));
+}
+
+#[test]
+fn u128_fields_reach_past_64_bits() {
+    let word = 0u128.new_unsigned(40, 80, (1 << 40) - 1).unwrap();
+    assert_eq!(word, ((1u128 << 40) - 1) << 80);
+    assert_eq!(word.get_unsigned(40, 80), Ok((1 << 40) - 1));
+
+    let word = word.new_signed(128, 0, i128::MIN).unwrap();
+    assert_eq!(word.get_signed(128, 0), Ok(i128::MIN));
+    assert_eq!(word.get_signed(1, 127), Ok(-1));
+    assert!(u128::fits_signed(i128::MAX, 128) && !u128::fits_signed(1 << 100, 100));
+}
+
+proptest! {
+    #[test]
+    fn u32_fields_agree_with_u64(word: u32, width in 0u64..=32, lsb in 0u64..=32, value: u32, signed: i32) {
+        prop_assume!(width + lsb <= 32);
+        let wide = u64::from(word);
+        prop_assert_eq!(word.get_unsigned(width, lsb).map(u64::from), get_unsigned(wide, width, lsb));
+        prop_assert_eq!(word.get_signed(width, lsb).map(i64::from), get_signed(wide, width, lsb));
+        prop_assert_eq!(word.new_unsigned(width, lsb, value).map(u64::from), new_unsigned(wide, width, lsb, u64::from(value)));
+        prop_assert_eq!(word.new_signed(width, lsb, signed).map(u64::from), new_signed(wide, width, lsb, i64::from(signed)));
+    }
+
+    #[test]
+    fn u128_fields_round_trip(word: u128, width in 0u64..=128, lsb in 0u64..=128, value: u128) {
+        prop_assume!(width + lsb <= 128);
+        let value = value & u128::low_mask(width);
+        let field = u128::low_mask(width).checked_shl(lsb as u32).unwrap_or(0);
+        let new = word.new_unsigned(width, lsb, value).unwrap();
+        prop_assert_eq!(new.get_unsigned(width, lsb), Ok(value));
+        prop_assert_eq!(new & !field, word & !field);
+    }
+}
@@ -0,0 +1,65 @@
+#![no_main]
+
+use std::io::Cursor;
+
+use array2::Array2;
+use libfuzzer_sys::fuzz_target;
+use rpeg::alpha::decode_alpha;
+use rpeg::chunks::{find_chunk, ALPHA_TAG};
+use rpeg::codec::{decode_legacy, read_legacy, Vcs};
+use rpeg::delta::decode_delta_recovering;
+use rpeg::integrity::GREY;
+use rpeg::metadata::Metadata;
+use rpeg::sequence::Sequence;
+use rpeg::tiling::{read_tiled, TiledImage};
+
+/// Images with more pixels than this are parsed but not decoded, to keep each run fast
+const MAX_FUZZ_PIXELS: usize = 1 << 20;
+
+/// Decodes every part of a tiled file, on top of `previous` if it holds residuals
+fn decode_tiled(tiled: &TiledImage, previous: Option<&Array2<Vcs>>) {
+    let _ = tiled.verify();
+    if tiled.delta {
+        let grey = Array2::new(tiled.width, tiled.height, GREY);
+        let _ = decode_delta_recovering(tiled, previous.unwrap_or(&grey));
+    } else {
+        let _ = tiled.decode_recovering();
+    }
+
+    if let Some(chunk) = find_chunk(&tiled.chunks, &ALPHA_TAG) {
+        let _ = decode_alpha(&chunk.data, tiled.width, tiled.height);
+    }
+    let _ = Metadata::from_chunks(&tiled.chunks);
+}
+
+fuzz_target!(|data: &[u8]| {
+    // Whatever the bytes are, every decoder must return an error rather than panic
+    if let Ok((_, width, height)) = read_legacy(data) {
+        if width * height <= MAX_FUZZ_PIXELS {
+            let _ = decode_legacy(data);
+        }
+    }
+
+    if let Ok(tiled) = read_tiled(data) {
+        if tiled.width * tiled.height <= MAX_FUZZ_PIXELS {
+            decode_tiled(&tiled, None);
+        }
+    }
+
+    if let Ok(mut sequence) = Sequence::open(Cursor::new(data)) {
+        let mut previous = None;
+        for index in 0..sequence.len() {
+            let entry = sequence.entries[index];
+            if entry.width * entry.height > MAX_FUZZ_PIXELS {
+                break;
+            }
+            previous = match sequence.decode_next(index, previous.as_ref()) {
+                Ok(decoded) => {
+                    decode_tiled(&decoded.tiled, previous.as_ref());
+                    Some(decoded.image)
+                }
+                Err(_) => None,
+            };
+        }
+    }
+});
@@ -0,0 +1,29 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rpeg::block_packing::{pack_block, unpack_block, BlockFields, BlockLayout};
+use rpeg::curve::Curve;
+use rpeg::delta::{pack_residual, unpack_residual};
+use rpeg::uncompress_dct::undo_dct_on_block_scaled;
+
+fuzz_target!(|word: u64| {
+    for layout in BlockLayout::PROFILES {
+        // Bits above the word size are not part of a block
+        let word = word & (u64::MAX >> (64 - layout.word_bits));
+
+        // Every word unpacks, and packing its fields gives the same word back
+        let BlockFields { a, b, c, d, pb, pr } = unpack_block(&layout, word).unwrap();
+        assert_eq!(pack_block::<u64>(&layout, a, b, c, d, pb, pr), Some(word));
+
+        let (a, b, c, d, pb, pr) = unpack_residual(&layout, word);
+        assert_eq!(pack_residual(&layout, a, b, c, d, pb, pr), word);
+
+        // Any word decodes to finite pixels on every curve
+        for curve in [Curve::Linear, Curve::Sqrt, Curve::Log] {
+            let block = undo_dct_on_block_scaled(vec![word], 2, 2, None, curve, &layout);
+            assert!(block
+                .iter_row_major()
+                .all(|(_, _, pixel)| pixel.y.is_finite() && pixel.pb.is_finite() && pixel.pr.is_finite()));
+        }
+    }
+});
@@ -0,0 +1,103 @@
+use array2::Array2;
+
+use crate::codec::Vcs;
+use crate::compress_dct::get_block;
+use crate::tiling::TileRect;
+
+/// Tag of the chunk holding every block's quantizer scale class
+pub const SCALE_TAG: [u8; 4] = *b"QSCL";
+
+/// Step multipliers for b, c and d, indexed by scale class.
+/// Smaller scales quantize more finely but clip sooner.
+pub const QUANT_SCALES: [f32; 4] = [0.25, 0.5, 1.0, 2.0];
+
+/// Class of the scale used by blocks without a signalled one
+pub const DEFAULT_CLASS: u8 = 2;
+
+/// Largest luma standard deviation each class is picked for, finest first.
+/// A 2x2 block's b, c and d never exceed its standard deviation, so each class
+/// is the finest one whose clipping point (0.3 times the scale) still covers it.
+const CLASS_LIMITS: [f32; 3] = [0.075, 0.15, 0.3];
+
+/// Classes packed into each byte of the chunk
+const CLASSES_PER_BYTE: usize = 4;
+
+/// The quantizer scale of a class
+pub fn scale_of_class(class: u8) -> f32 {
+    QUANT_SCALES[class as usize % QUANT_SCALES.len()]
+}
+
+/// Picks the scale class of a 2x2 block from the variance of its luma.
+/// Flat blocks, where banding shows, get a finer step than busy ones,
+/// which hide the error and need the wider range.
+pub fn block_class(block: &Array2<Vcs>) -> u8 {
+    let mean = block.iter_row_major().map(|(_, _, pixel)| pixel.y).sum::<f32>() / 4.0;
+    let variance = block.iter_row_major().map(|(_, _, pixel)| (pixel.y - mean).powi(2)).sum::<f32>() / 4.0;
+    let deviation = variance.sqrt();
+
+    CLASS_LIMITS
+        .iter()
+        .position(|&limit| deviation <= limit)
+        .unwrap_or(CLASS_LIMITS.len()) as u8
+}
+
+/// Picks a scale class for every 2x2 block of an even sized image.
+/// The result has one entry per block.
+pub fn block_classes(img: &Array2<Vcs>) -> Array2<u8> {
+    let (blocks_wide, blocks_high) = (img.width() / 2, img.height() / 2);
+    let mut classes = Vec::with_capacity(blocks_wide * blocks_high);
+
+    for y in (0..img.height()).step_by(2) {
+        for x in (0..img.width()).step_by(2) {
+            classes.push(block_class(&get_block(img, x, y)));
+        }
+    }
+
+    Array2::from_row_major(blocks_wide, blocks_high, &classes)
+}
+
+/// Copies the classes of the blocks covered by a tile
+pub fn tile_classes(classes: &Array2<u8>, rect: &TileRect) -> Array2<u8> {
+    let (x0, y0) = (rect.x / 2, rect.y / 2);
+    let (blocks_wide, blocks_high) = (rect.width / 2, rect.height / 2);
+    let mut data = Vec::with_capacity(blocks_wide * blocks_high);
+
+    for y in y0..y0 + blocks_high {
+        for x in x0..x0 + blocks_wide {
+            data.push(*classes.get(x, y).unwrap());
+        }
+    }
+
+    Array2::from_row_major(blocks_wide, blocks_high, &data)
+}
+
+/// Packs the classes in row-major order, two bits each with the first class in the high bits
+pub fn pack_classes(classes: &Array2<u8>) -> Vec<u8> {
+    let values: Vec<u8> = classes.iter_row_major().map(|(_, _, &class)| class).collect();
+
+    values
+        .chunks(CLASSES_PER_BYTE)
+        .map(|group| {
+            group
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, &class)| byte | (class & 0b11) << (6 - 2 * i))
+        })
+        .collect()
+}
+
+/// Unpacks the classes written by `pack_classes` for an image of the given block dimensions
+pub fn unpack_classes(bytes: &[u8], blocks_wide: usize, blocks_high: usize) -> Result<Array2<u8>, String> {
+    let count = blocks_wide
+        .checked_mul(blocks_high)
+        .ok_or("image is too large")?;
+    if bytes.len() != count.div_ceil(CLASSES_PER_BYTE) {
+        return Err("quantizer scale chunk does not match the image size".to_string());
+    }
+
+    let classes: Vec<u8> = (0..count)
+        .map(|i| bytes[i / CLASSES_PER_BYTE] >> (6 - 2 * (i % CLASSES_PER_BYTE)) & 0b11)
+        .collect();
+
+    Ok(Array2::from_row_major(blocks_wide, blocks_high, &classes))
+}
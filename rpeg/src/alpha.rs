@@ -0,0 +1,125 @@
+use array2::Array2;
+
+/// Number of bits kept per alpha value in quantized mode
+const QUANTIZED_BITS: u32 = 4;
+
+/// Longest run a single run-length pair can describe
+const MAX_RUN: usize = u8::MAX as usize;
+
+// Enum listing how the alpha plane can be coded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlphaMode {
+    #[default]
+    Lossless,
+    Quantized,
+}
+
+impl AlphaMode {
+    /// Parses a mode name given on the command line
+    pub fn parse(name: &str) -> Result<AlphaMode, String> {
+        match name {
+            "lossless" => Ok(AlphaMode::Lossless),
+            "quantized" => Ok(AlphaMode::Quantized),
+            _ => Err(format!("unknown alpha mode `{}`, expected lossless or quantized", name)),
+        }
+    }
+
+    /// The byte recorded in the stream for this mode
+    fn id(self) -> u8 {
+        match self {
+            AlphaMode::Lossless => 0,
+            AlphaMode::Quantized => 1,
+        }
+    }
+
+    /// The mode recorded by `id`
+    fn from_id(id: u8) -> Result<AlphaMode, String> {
+        match id {
+            0 => Ok(AlphaMode::Lossless),
+            1 => Ok(AlphaMode::Quantized),
+            _ => Err(format!("unknown alpha mode {}", id)),
+        }
+    }
+}
+
+/// Trims an alpha plane to the given (even) dimensions, matching the colour planes
+pub fn even_alpha(alpha: &Array2<u8>, width: usize, height: usize) -> Array2<u8> {
+    let mut data = Vec::with_capacity(width * height);
+
+    for y in 0..height {
+        for x in 0..width {
+            data.push(*alpha.get(x, y).unwrap());
+        }
+    }
+
+    Array2::from_row_major(width, height, &data)
+}
+
+/// Reduces an alpha value to `QUANTIZED_BITS` bits
+fn quantize(value: u8) -> u8 {
+    let levels = (1u32 << QUANTIZED_BITS) - 1;
+    ((value as u32 * levels + 127) / 255) as u8
+}
+
+/// Expands a quantized alpha value back to 0..255
+fn dequantize(level: u8) -> u8 {
+    let levels = (1u32 << QUANTIZED_BITS) - 1;
+    ((level as u32 * 255 + levels / 2) / levels) as u8
+}
+
+/// Codes an alpha plane in row-major order as (run length, value) byte pairs,
+/// preceded by the mode byte. Quantized mode keeps `QUANTIZED_BITS` bits per value,
+/// which makes the runs in soft edges much longer.
+pub fn encode_alpha(alpha: &Array2<u8>, mode: AlphaMode) -> Vec<u8> {
+    let mut coded = vec![mode.id()];
+    let mut values = alpha.iter_row_major().map(|(_, _, &value)| match mode {
+        AlphaMode::Lossless => value,
+        AlphaMode::Quantized => quantize(value),
+    });
+
+    let Some(mut current) = values.next() else {
+        return coded;
+    };
+    let mut run = 1;
+
+    for value in values {
+        if value == current && run < MAX_RUN {
+            run += 1;
+        } else {
+            coded.extend([run as u8, current]);
+            current = value;
+            run = 1;
+        }
+    }
+    coded.extend([run as u8, current]);
+
+    coded
+}
+
+/// Decodes an alpha plane written by `encode_alpha`
+pub fn decode_alpha(coded: &[u8], width: usize, height: usize) -> Result<Array2<u8>, String> {
+    let (&id, pairs) = coded.split_first().ok_or("empty alpha plane")?;
+    let mode = AlphaMode::from_id(id)?;
+
+    if !pairs.len().is_multiple_of(2) {
+        return Err("alpha plane has a dangling byte".to_string());
+    }
+
+    let mut data = Vec::with_capacity(width * height);
+    for pair in pairs.chunks(2) {
+        let value = match mode {
+            AlphaMode::Lossless => pair[1],
+            AlphaMode::Quantized => dequantize(pair[1]),
+        };
+        if data.len() + pair[0] as usize > width * height {
+            return Err("alpha plane is longer than the image".to_string());
+        }
+        data.extend(std::iter::repeat_n(value, pair[0] as usize));
+    }
+
+    if data.len() != width * height {
+        return Err("alpha plane is shorter than the image".to_string());
+    }
+
+    Ok(Array2::from_row_major(width, height, &data))
+}
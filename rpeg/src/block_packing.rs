@@ -1,29 +1,273 @@
-use bitpack::bitpack;
-
-/// Packs values a, b, c, d, pb, and pr into a 32-bit word
-pub fn pack_block(a: u64, b: i64, c: i64, d: i64, pb: u64, pr: u64) -> u32 {
-    let mut word = 0u64;
-
-    // Pack values into a 64-bit word using bitpack functions
-    word = bitpack::newu(word, 9, 23, a).unwrap();
-    word = bitpack::news(word, 5, 18, b).unwrap(); 
-    word = bitpack::news(word, 5, 13, c).unwrap(); 
-    word = bitpack::news(word, 5, 8, d).unwrap(); 
-    word = bitpack::newu(word, 4, 4, pb).unwrap(); 
-    word = bitpack::newu(word, 4, 0, pr).unwrap(); 
-
-    word as u32
-}
-
-/// Unpacks a packed 32-bit word into its constituent parts
-pub fn unpack_block(packed_word: u32) -> (Option<u64>, Option<i64>, Option<i64>, Option<i64>, Option<u64>, Option<u64>) {
-    
-    // Unpack values from the packed word using bitpack functions
-    let a = bitpack::getu(packed_word as u64, 9, 23);
-    let b = bitpack::gets(packed_word as u64, 5, 18);
-    let c = bitpack::gets(packed_word as u64, 5, 13);
-    let d = bitpack::gets(packed_word as u64, 5, 8);
-    let pb = bitpack::getu(packed_word as u64, 4, 4);
-    let pr = bitpack::getu(packed_word as u64, 4, 0);
-    (a, b, c, d, pb, pr)
+use bitpack::bitfield;
+use bitpack::bitfield::FieldSpec;
+use bitpack::bulk;
+use bitpack::serial::{self, ByteOrder};
+use bitpack::word::Word;
+use csc411_arith::{chroma_of_index, index_of_chroma};
+
+/// Width of the chroma fields that use the csc411 chroma table
+const TABLE_CHROMA_WIDTH: u64 = 4;
+
+/// Largest magnitude of a chroma value coded by a wider field
+const CHROMA_RANGE: f32 = 0.5;
+
+bitfield! {
+    /// The fields of the original 32-bit word
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct LegacyBlock: u32 {
+        pub a: unsigned(9, 23),
+        pub b: signed(5, 18),
+        pub c: signed(5, 13),
+        pub d: signed(5, 8),
+        pub pb: unsigned(4, 4),
+        pub pr: unsigned(4, 0),
+    }
+}
+
+bitfield! {
+    /// The fields of a 48-bit word, held in the low bits of a `u64`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct HighBlock: u64 {
+        pub a: unsigned(12, 36),
+        pub b: signed(8, 28),
+        pub c: signed(8, 20),
+        pub d: signed(8, 12),
+        pub pb: unsigned(6, 6),
+        pub pr: unsigned(6, 0),
+    }
+}
+
+bitfield! {
+    /// The fields of a 64-bit word
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct NearLosslessBlock: u64 {
+        pub a: unsigned(13, 51),
+        pub b: signed(11, 40),
+        pub c: signed(11, 29),
+        pub d: signed(11, 18),
+        pub pb: unsigned(9, 9),
+        pub pr: unsigned(9, 0),
+    }
+}
+
+// Struct holding the values of a block's fields, whatever layout they came from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockFields {
+    pub a: u64,
+    pub b: i64,
+    pub c: i64,
+    pub d: i64,
+    pub pb: u64,
+    pub pr: u64,
+}
+
+// Struct describing where one field sits in a packed word
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    pub width: u64,
+    pub lsb: u64,
+    pub signed: bool,
+}
+
+// Struct describing the fields of a packed block word.
+// `b`, `c` and `d` share a width, which sets how many levels they can take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockLayout {
+    pub name: &'static str,
+    pub word_bits: usize,
+    pub a: Field,
+    pub b: Field,
+    pub c: Field,
+    pub d: Field,
+    pub pb: Field,
+    pub pr: Field,
+}
+
+impl Field {
+    /// An unsigned field of `width` bits starting at `lsb`
+    pub const fn unsigned(width: u64, lsb: u64) -> Field {
+        Field { width, lsb, signed: false }
+    }
+
+    /// A signed field of `width` bits starting at `lsb`
+    pub const fn signed(width: u64, lsb: u64) -> Field {
+        Field { width, lsb, signed: true }
+    }
+
+    /// The field described by a `bitfield!` declaration
+    pub const fn from_spec(spec: FieldSpec) -> Field {
+        Field { width: spec.width, lsb: spec.lsb, signed: spec.signed }
+    }
+
+    /// The largest value the field can hold
+    pub fn max(self) -> i64 {
+        if self.signed { (1 << (self.width - 1)) - 1 } else { (1 << self.width) - 1 }
+    }
+
+    /// Replaces the field in `word` with `value`, or returns None if it does
+    /// not fit the field or the field does not fit the word
+    pub fn put<W: Word>(self, word: W, value: i64) -> Option<W> {
+        if self.signed {
+            word.new_signed(self.width, self.lsb, W::Signed::try_from(value).ok()?).ok()
+        } else {
+            let value = W::try_from(u64::try_from(value).ok()?).ok()?;
+            word.new_unsigned(self.width, self.lsb, value).ok()
+        }
+    }
+
+    /// Reads the field from `word`, or returns None if the field does not fit the word
+    pub fn get<W: Word>(self, word: W) -> Option<i64> {
+        if self.signed {
+            word.get_signed(self.width, self.lsb).ok()?.try_into().ok()
+        } else {
+            let value: u64 = word.get_unsigned(self.width, self.lsb).ok()?.try_into().ok()?;
+            i64::try_from(value).ok()
+        }
+    }
+
+    /// Reads the field from every word. A field that does not fit the words reads as zero.
+    pub fn get_all<W: Word>(self, words: &[W]) -> Vec<i64> {
+        let values = if self.signed {
+            bulk::unpack_signed(words, self.width, self.lsb)
+        } else {
+            bulk::unpack_unsigned(words, self.width, self.lsb)
+                .map(|values| values.into_iter().map(|value| value as i64).collect())
+        };
+        values.unwrap_or_else(|_| vec![0; words.len()])
+    }
+
+    /// Quantizes an average chroma value to an index.
+    /// Fields of 4 bits use the csc411 chroma table, wider ones are uniform
+    /// with zero at the middle index.
+    pub fn chroma_index(self, value: f32) -> u64 {
+        if self.width == TABLE_CHROMA_WIDTH {
+            return index_of_chroma(value) as u64;
+        }
+        let middle = self.max() / 2;
+        let steps = (value.clamp(-CHROMA_RANGE, CHROMA_RANGE) / CHROMA_RANGE * middle as f32).round() as i64;
+        (middle + steps) as u64
+    }
+
+    /// The chroma value an index written by `chroma_index` stands for.
+    /// Indices too large for the field are clamped to its largest.
+    pub fn chroma_value(self, index: u64) -> f32 {
+        let index = index.min(self.max() as u64);
+        if self.width == TABLE_CHROMA_WIDTH {
+            return chroma_of_index(index as usize);
+        }
+        let middle = self.max() / 2;
+        (index as i64 - middle) as f32 / middle as f32 * CHROMA_RANGE
+    }
+}
+
+impl BlockLayout {
+    /// The original 32-bit word: a:9, b/c/d:5 and pb/pr:4 bits
+    pub const LEGACY: BlockLayout = BlockLayout::from_fields("legacy", 32, LegacyBlock::FIELDS);
+
+    /// A 48-bit word: a:12, b/c/d:8 and pb/pr:6 bits
+    pub const HIGH: BlockLayout = BlockLayout::from_fields("high", 48, HighBlock::FIELDS);
+
+    /// A 64-bit word: a:13, b/c/d:11 and pb/pr:9 bits
+    pub const NEAR_LOSSLESS: BlockLayout = BlockLayout::from_fields("near-lossless", 64, NearLosslessBlock::FIELDS);
+
+    /// Every built-in layout, smallest first
+    pub const PROFILES: [BlockLayout; 3] = [BlockLayout::LEGACY, BlockLayout::HIGH, BlockLayout::NEAR_LOSSLESS];
+
+    /// The layout of a block struct declared with `bitfield!`, whose fields
+    /// are `a`, `b`, `c`, `d`, `pb` and `pr` in that order
+    const fn from_fields(name: &'static str, word_bits: usize, fields: &[FieldSpec]) -> BlockLayout {
+        BlockLayout {
+            name,
+            word_bits,
+            a: Field::from_spec(fields[0]),
+            b: Field::from_spec(fields[1]),
+            c: Field::from_spec(fields[2]),
+            d: Field::from_spec(fields[3]),
+            pb: Field::from_spec(fields[4]),
+            pr: Field::from_spec(fields[5]),
+        }
+    }
+
+    /// The fields in the order `a`, `b`, `c`, `d`, `pb`, `pr`
+    pub fn fields(&self) -> [Field; 6] {
+        [self.a, self.b, self.c, self.d, self.pb, self.pr]
+    }
+
+    /// Parses a profile name given on the command line
+    pub fn parse(name: &str) -> Result<BlockLayout, String> {
+        BlockLayout::PROFILES
+            .into_iter()
+            .find(|layout| layout.name == name)
+            .ok_or(format!("unknown layout `{}`, expected legacy, high or near-lossless", name))
+    }
+
+    /// The profile whose words have `word_bits` bits, as recorded in a file header
+    pub fn from_word_bits(word_bits: usize) -> Result<BlockLayout, String> {
+        BlockLayout::PROFILES
+            .into_iter()
+            .find(|layout| layout.word_bits == word_bits)
+            .ok_or(format!("unsupported word size of {} bits", word_bits))
+    }
+
+    /// Bytes taken by each word in a file
+    pub fn word_bytes(&self) -> usize {
+        self.word_bits / 8
+    }
+
+    /// The layout of a residual word, which is the same except that `a` is signed
+    pub fn residual(&self) -> BlockLayout {
+        BlockLayout { a: Field { signed: true, ..self.a }, ..*self }
+    }
+
+    /// The number `a` is multiplied by before rounding
+    pub fn a_scale(&self) -> f32 {
+        Field::unsigned(self.a.width, 0).max() as f32
+    }
+
+    /// The largest level of `b`, `c` and `d`
+    pub fn max_level(&self) -> i64 {
+        self.b.max()
+    }
+
+    /// Converts words to the big-endian bytes stored in a file
+    pub fn words_to_bytes(&self, words: &[u64]) -> Vec<u8> {
+        serial::words_to_bytes(words, self.word_bits as u64, ByteOrder::BigEndian).expect("words fit their layout")
+    }
+
+    /// Reads the words held whole in big-endian bytes written by `words_to_bytes`
+    pub fn words_from_bytes(&self, bytes: &[u8]) -> Vec<u64> {
+        serial::words_from_bytes(bytes, self.word_bits as u64, ByteOrder::BigEndian).expect("layouts fit in 64 bits")
+    }
+}
+
+impl Default for BlockLayout {
+    fn default() -> BlockLayout {
+        BlockLayout::LEGACY
+    }
+}
+
+/// Packs values a, b, c, d, pb, and pr into a word laid out by `layout`.
+/// Returns None if a value does not fit its field or `W` has fewer bits than
+/// the layout's words, so callers should clamp values to the layout first.
+pub fn pack_block<W: Word>(layout: &BlockLayout, a: u64, b: i64, c: i64, d: i64, pb: u64, pr: u64) -> Option<W> {
+    let values = [i64::try_from(a).ok()?, b, c, d, i64::try_from(pb).ok()?, i64::try_from(pr).ok()?];
+    layout
+        .fields()
+        .into_iter()
+        .zip(values)
+        .try_fold(W::ZERO, |word, (field, value)| field.put(word, value))
+}
+
+/// Unpacks a word laid out by `layout` into its constituent parts.
+/// Returns None if `W` has fewer bits than the layout's words.
+pub fn unpack_block<W: Word>(layout: &BlockLayout, packed_word: W) -> Option<BlockFields> {
+    let [a, b, c, d, pb, pr] = layout.fields().map(|field| field.get(packed_word));
+    let unsigned = |value: Option<i64>| value.and_then(|value| u64::try_from(value).ok());
+    Some(BlockFields { a: unsigned(a)?, b: b?, c: c?, d: d?, pb: unsigned(pb)?, pr: unsigned(pr)? })
+}
+
+/// Unpacks every word laid out by `layout` into one vector per field, in the
+/// order of `BlockLayout::fields`
+pub fn unpack_blocks<W: Word>(layout: &BlockLayout, words: &[W]) -> [Vec<i64>; 6] {
+    layout.fields().map(|field| field.get_all(words))
 }
@@ -0,0 +1,76 @@
+use std::io::Write;
+
+use crate::integrity::crc32;
+
+/// Tag of the chunk holding the coded alpha plane
+pub const ALPHA_TAG: [u8; 4] = *b"ALPH";
+
+/// Bytes of framing around each chunk's data: tag, length and checksum
+const FRAMING: usize = 12;
+
+// Struct representing a tagged chunk of extra data stored after the tiles
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub tag: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+impl Chunk {
+    /// The tag as text, for messages
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.tag).into_owned()
+    }
+}
+
+/// Finds the first chunk with the given tag
+pub fn find_chunk<'a>(chunks: &'a [Chunk], tag: &[u8; 4]) -> Option<&'a Chunk> {
+    chunks.iter().find(|chunk| &chunk.tag == tag)
+}
+
+/// Computes the CRC-32 covering a chunk's tag and data
+fn chunk_checksum(tag: &[u8; 4], data: &[u8]) -> u32 {
+    let mut checked = tag.to_vec();
+    checked.extend(data);
+    crc32(&checked)
+}
+
+/// Writes each chunk as its tag, data length, data and a CRC-32 of the tag and data
+pub fn write_chunks(out: &mut impl Write, chunks: &[Chunk]) -> std::io::Result<()> {
+    for chunk in chunks {
+        out.write_all(&chunk.tag)?;
+        out.write_all(&(chunk.data.len() as u32).to_be_bytes())?;
+        out.write_all(&chunk.data)?;
+        out.write_all(&chunk_checksum(&chunk.tag, &chunk.data).to_be_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Reads chunks until the end of the bytes.
+/// Stops at the first damaged chunk, returning the chunks read so far and the reason.
+pub fn read_chunks(bytes: &[u8]) -> (Vec<Chunk>, Option<String>) {
+    let mut chunks = Vec::new();
+    let mut rest = bytes;
+
+    while !rest.is_empty() {
+        if rest.len() < FRAMING {
+            return (chunks, Some("truncated chunk header".to_string()));
+        }
+
+        let tag: [u8; 4] = rest[0..4].try_into().unwrap();
+        let len = u32::from_be_bytes(rest[4..8].try_into().unwrap()) as usize;
+        let Some(checksum) = rest.get(8 + len..FRAMING + len) else {
+            return (chunks, Some(format!("chunk {} runs past the end of the file", String::from_utf8_lossy(&tag))));
+        };
+
+        let chunk = Chunk { tag, data: rest[8..8 + len].to_vec() };
+        if chunk_checksum(&chunk.tag, &chunk.data) != u32::from_be_bytes(checksum.try_into().unwrap()) {
+            return (chunks, Some(format!("chunk {} failed its checksum", chunk.name())));
+        }
+
+        chunks.push(chunk);
+        rest = &rest[FRAMING + len..];
+    }
+
+    (chunks, None)
+}
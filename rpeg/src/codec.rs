@@ -2,15 +2,51 @@ use crate::compress_pixels;
 use crate::uncompress_dct;
 use crate::uncompress_pixels;
 use crate::compress_dct;
+use crate::tiling;
+use crate::integrity;
+use crate::info;
+use crate::debug_dump;
+use crate::formats;
+use crate::alpha;
+use crate::chunks;
+use crate::metadata;
+use crate::sequence;
+use crate::delta;
+use crate::adaptive;
+use crate::rdo;
+use crate::curve;
+use crate::block_packing;
 
-use csc411_image::{Read, Write, RgbImage};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::io::{self, BufWriter, Read, Write};
+use std::time::{Duration, UNIX_EPOCH};
+
+use csc411_image::Rgb;
 use array2::Array2;
-use csc411_rpegio::{input_rpeg_data,output_rpeg_data};
-use compress_dct::dct_on_block;
+use csc411_rpegio::output_rpeg_data;
+use bitpack::serial::{self, ByteOrder};
+use compress_dct::dct_on_block_scaled;
 use compress_pixels::{even_height, even_width, even_array2, convert_to_floats, convert_to_vcs};
 use uncompress_dct::undo_dct_on_block;
 use uncompress_pixels::vcs_to_rgb;
+use tiling::{encode_tiles_scaled, is_tiled, parse_numbers, read_line, read_tiled, write_delta, write_tiled};
+use integrity::{check_dimensions, check_legacy_length, fill_grey, report, Damage};
+use info::FileInfo;
+use debug_dump::dump_planes;
+use formats::{load_image, save_image, Format, Image};
+use alpha::{decode_alpha, encode_alpha, even_alpha, AlphaMode};
+use chunks::{find_chunk, Chunk, ALPHA_TAG};
+use metadata::Metadata;
+use sequence::{is_sequence, DecodedFrame, FrameEntry, Sequence, SequenceWriter};
+use delta::{decode_delta, encode_delta, split_residual_tile};
+use adaptive::{block_classes, pack_classes, SCALE_TAG};
+use rdo::Quantizer;
+use curve::{Curve, CURVE_TAG};
+use block_packing::BlockLayout;
 
+/// Header line identifying a legacy rpeg file, as written by csc411_rpegio
+const LEGACY_MAGIC: &str = "Compressed image format 2";
 
 
 // Struct representing RGB pixel values as floats
@@ -29,15 +65,38 @@ pub struct Vcs {
     pub pr: f32,
 }
 
+// Struct holding the choices made when compressing.
+// `metadata` is stored alongside any comments found in the input.
+// When packing a sequence with a `keyframe_interval`, frames between
+// keyframes are coded as residuals against the frame before them.
+// `adaptive` picks a quantizer scale for every block of a keyframe,
+// `quantizer` picks the level of every coefficient, `curve` maps
+// b, c and d onto their levels and `layout` sets the size of each field.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompressOptions {
+    pub tile_size: Option<usize>,
+    pub alpha_mode: AlphaMode,
+    pub metadata: Metadata,
+    pub keyframe_interval: Option<usize>,
+    pub adaptive: bool,
+    pub quantizer: Quantizer,
+    pub curve: Curve,
+    pub layout: BlockLayout,
+}
 
+// Struct holding the choices made when decompressing
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecompressOptions {
+    pub output: Option<String>,
+    pub recover: bool,
+    pub metadata_dir: Option<String>,
+}
 
-// Function to compress an image
-pub fn compress(filename: Option<&str>) {
-    // Read the RGB image from file
-    let img = RgbImage::read(filename.as_deref()).unwrap();
-
-    // Convert image pixels to a 2D array
-    let img_array = Array2::from_row_major( img.width as usize, img.height as usize, &img.pixels);
+// Function to read an image trimmed to even dimensions, along with
+// its alpha plane and header comments if it has them
+fn read_even_image(filename: Option<&str>) -> Result<Image, String> {
+    // Read the RGB image from file in any supported format
+    let Image { pixels: img_array, alpha, comments } = load_image(filename)?;
     // For every pixel seen in the image, they
     // should be stored in the row major in the
     // correct spot in the array2 class
@@ -45,6 +104,9 @@ pub fn compress(filename: Option<&str>) {
     // Ensure even width and height for blocking
     let width:usize = even_width(img_array.clone());
     let height:usize = even_height(img_array.clone());
+    if width == 0 || height == 0 {
+        return Err("image must be at least 2x2 pixels".to_string());
+    }
 
     // Change array to even dimensions
     let even_img_array = even_array2(img_array.clone(), width, height);
@@ -52,6 +114,23 @@ pub fn compress(filename: Option<&str>) {
     // of width and height should be
     // an even number
 
+    // Trim the alpha plane to match
+    let alpha = alpha.map(|alpha| even_alpha(&alpha, width, height));
+
+    Ok(Image { pixels: even_img_array, alpha, comments })
+}
+
+// Function to get the modification time of a file as the time since the Unix epoch.
+// Returns None if the file or its time cannot be read.
+fn modified_since_epoch(path: &Path) -> Option<Duration> {
+    fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+}
+
+// Function to convert an even sized image into an array of Vcs values
+fn rgb_to_vcs(even_img_array: Array2<Rgb>) -> Array2<Vcs> {
     // Convert pixel values to floats
     let float_img_array = convert_to_floats(even_img_array);
     // For every pixel seen even image,
@@ -63,69 +142,553 @@ pub fn compress(filename: Option<&str>) {
     // the even image, they should be 
     // represented by Vcs
 
+    vcs_img_array
+}
+
+// Function to compress an image with `quantizer` while writing per-component debug images to `dir`
+pub fn compress_with_dump(filename: Option<&str>, dir: &str, quantizer: Quantizer) -> Result<(), String> {
+    let image = read_even_image(filename)?;
+
+    // The dump is written in the legacy format, which has nowhere to keep alpha
+    if image.alpha.is_some() {
+        return Err("--dump cannot keep the alpha channel of the input".to_string());
+    }
+    if !image.comments.is_empty() {
+        eprintln!("Dropping the header comments of the input, which the legacy format cannot hold");
+    }
+    let even_img_array = image.pixels;
+    let vcs_img_array = rgb_to_vcs(even_img_array.clone());
+    let (width, height) = (vcs_img_array.width(), vcs_img_array.height());
+
+    // Compress the image using Discrete Cosine Transform
+    let layout = BlockLayout::LEGACY;
+    let compressed_vec = dct_on_block_scaled(vcs_img_array.clone(), height, width, None, quantizer, Curve::Linear, &layout);
+
+    // Decompress again so the error can be measured against the original
+    let decoded_rgb_array = vcs_to_rgb(&undo_dct_on_block(compressed_vec.clone(), height, width));
+
+    dump_planes(Path::new(dir), &vcs_img_array, &compressed_vec, &even_img_array, &decoded_rgb_array)?;
+
+    // Output the compressed data
+    let compressed_img: Vec<[u8; 4]> = compressed_vec.into_iter().map(u32::to_be_bytes).collect();
+    output_rpeg_data(&compressed_img, width, height).map_err(|e| e.to_string())
+}
+
+// Function to compress an image.
+// Images with alpha or metadata, or when a tile size, adaptive
+// quantization, a curve other than linear or a wider layout is asked
+// for, are written as a tiled file; anything else uses the legacy format.
+pub fn compress(filename: Option<&str>, options: &CompressOptions) -> Result<(), String> {
+    let Image { pixels: even_img_array, alpha, comments } = read_even_image(filename)?;
+    let (width, height) = (even_img_array.width(), even_img_array.height());
+    let vcs_img_array = rgb_to_vcs(even_img_array);
+
+    // Header comments alone do not switch the output to the tiled format
+    let plain = !options.adaptive && options.curve == Curve::Linear && options.layout == BlockLayout::LEGACY;
+    if options.tile_size.is_none() && plain && alpha.is_none() && options.metadata.is_empty() {
+        if !comments.is_empty() {
+            eprintln!("Dropping the header comments of the input, which the legacy format cannot hold");
+        }
+        return compress_legacy(vcs_img_array, width, height, options.quantizer);
+    }
+
+    // Comments from the input come before any given on the command line
+    let mut metadata = options.metadata.clone();
+    metadata.comments.splice(0..0, comments);
+
+    // Stamp the file with when its input was last changed, which is the same on every run
+    if let Some(name) = filename {
+        let modified = modified_since_epoch(Path::new(name));
+        metadata.created = metadata.created.or(modified.map(|time| time.as_secs()));
+    }
+
+    let bytes = encode_tiled(&vcs_img_array, None, alpha, metadata, options)?;
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    out.write_all(&bytes).and_then(|_| out.flush()).map_err(|e| e.to_string())
+}
+
+// Function to compress an image into a complete tiled file held in memory.
+// Given the reconstruction of the previous frame, the tiles hold residuals against it.
+fn encode_tiled(
+    vcs_img_array: &Array2<Vcs>,
+    previous: Option<&Array2<Vcs>>,
+    alpha: Option<Array2<u8>>,
+    metadata: Metadata,
+    options: &CompressOptions,
+) -> Result<Vec<u8>, String> {
+    let (width, height) = (vcs_img_array.width(), vcs_img_array.height());
+
+    // Without a tile size the whole image is one tile
+    let tile_size = options.tile_size.unwrap_or(width.max(height).max(2));
+    if tile_size == 0 || !tile_size.is_multiple_of(2) {
+        return Err("tile size must be a positive even number".to_string());
+    }
+
+    // Pick each block's quantizer scale from the whole image, so tiles agree
+    let classes = (options.adaptive && previous.is_none()).then(|| block_classes(vcs_img_array));
+
+    // Compress every tile on its own
+    let tiles = match previous {
+        Some(previous) => {
+            encode_delta(vcs_img_array, previous, tile_size, options.quantizer, options.curve, &options.layout)
+        }
+        None => {
+            let classes = classes.as_ref();
+            encode_tiles_scaled(vcs_img_array, tile_size, classes, options.quantizer, options.curve, &options.layout)
+        }
+    };
+    // For every tile in the image, its
+    // blocks should be compressed without
+    // looking at any other tile
+
+    // Code the alpha plane separately from the colour blocks
+    let mut chunks: Vec<Chunk> = alpha
+        .map(|alpha| Chunk { tag: ALPHA_TAG, data: encode_alpha(&alpha, options.alpha_mode) })
+        .into_iter()
+        .collect();
+    if let Some(classes) = &classes {
+        chunks.push(Chunk { tag: SCALE_TAG, data: pack_classes(classes) });
+    }
+    if options.curve != Curve::Linear {
+        chunks.push(Chunk { tag: CURVE_TAG, data: options.curve.to_chunk_data() });
+    }
+    chunks.extend(metadata.to_chunks());
+
+    // Lay out the header, tile index table, tiles and chunks
+    let mut bytes = Vec::new();
+    match previous {
+        Some(_) => write_delta(&mut bytes, width, height, tile_size, &options.layout, &tiles, &chunks),
+        None => write_tiled(&mut bytes, width, height, tile_size, &options.layout, &tiles, &chunks),
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+// Function to compress every image in a directory into one sequence file.
+// Frames are taken in filename order and stamped with each file's
+// modification time. With `append` set they are added to the end of `output`,
+// carrying on from its last frame when coding residuals.
+pub fn pack(dir: &str, output: Option<&str>, append: bool, options: &CompressOptions) -> Result<(), String> {
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| format!("{}: {}", dir, e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && Format::has_image_extension(&path.to_string_lossy()))
+        .collect();
+    paths.sort();
+
+    if paths.is_empty() {
+        return Err(format!("{}: no images to pack", dir));
+    }
+
+    match (output, append) {
+        (Some(name), true) => {
+            // Residuals carry on from the reconstruction of the file's last frame
+            let mut start = None;
+            if options.keyframe_interval.is_some() {
+                let file = File::open(name).map_err(|e| format!("{}: {}", name, e))?;
+                let mut sequence = Sequence::open(file)?;
+                if let Some(last) = sequence.len().checked_sub(1) {
+                    let decoded = sequence.decode_frame(last)?;
+                    if let Some(region) = decoded.damage.first() {
+                        return Err(format!("{}: {}", name, region.reason));
+                    }
+                    start = Some((decoded.image, last - sequence.keyframe_before(last) + 1));
+                }
+            }
+
+            let mut writer = SequenceWriter::append_to(Path::new(name))?;
+            pack_frames(&mut writer, &paths, start, options)?;
+            writer.finish().map_err(|e| e.to_string())?;
+        }
+        (Some(name), false) => {
+            let file = File::create(name).map_err(|e| format!("{}: {}", name, e))?;
+            let mut writer = SequenceWriter::new(BufWriter::new(file)).map_err(|e| e.to_string())?;
+            pack_frames(&mut writer, &paths, None, options)?;
+            writer.finish().map_err(|e| e.to_string())?;
+        }
+        (None, true) => return Err("--append needs an output file".to_string()),
+        (None, false) => {
+            let mut writer = SequenceWriter::new(BufWriter::new(io::stdout().lock())).map_err(|e| e.to_string())?;
+            pack_frames(&mut writer, &paths, None, options)?;
+            writer.finish().map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+// Function to compress each image file and append it to the sequence as a frame.
+// `start` holds the reconstruction of the frame before the first one and how
+// many frames have been coded since the last keyframe, including that frame.
+fn pack_frames<W: Write>(
+    writer: &mut SequenceWriter<W>,
+    paths: &[PathBuf],
+    start: Option<(Array2<Vcs>, usize)>,
+    options: &CompressOptions,
+) -> Result<(), String> {
+    let (mut previous, mut since_keyframe) = match start {
+        Some((previous, since_keyframe)) => (Some(previous), since_keyframe),
+        None => (None, 0),
+    };
+
+    for path in paths {
+        let name = path.to_string_lossy();
+        let Image { pixels, alpha, comments } = read_even_image(Some(&name))?;
+
+        // Comments from the input come before any given on the command line
+        let mut metadata = options.metadata.clone();
+        metadata.comments.splice(0..0, comments);
+        let modified = modified_since_epoch(path);
+        metadata.created = metadata.created.or(modified.map(|time| time.as_secs()));
+        let timestamp = modified.map_or(0, |time| time.as_millis() as u64);
+
+        let vcs_img_array = rgb_to_vcs(pixels);
+
+        // Code a residual frame unless a keyframe is due or the frame changed size
+        let reference = match (options.keyframe_interval, &previous) {
+            (Some(interval), Some(previous))
+                if since_keyframe < interval
+                    && (previous.width(), previous.height()) == (vcs_img_array.width(), vcs_img_array.height()) =>
+            {
+                Some(previous)
+            }
+            _ => None,
+        };
+        let frame = encode_tiled(&vcs_img_array, reference, alpha, metadata, options)?;
+        since_keyframe = if reference.is_some() { since_keyframe + 1 } else { 1 };
+
+        // Residuals are always taken against what the decoder will see, so errors do not build up
+        if options.keyframe_interval.is_some() {
+            let tiled = read_tiled(&frame)?;
+            previous = Some(match reference {
+                Some(reference) => decode_delta(&tiled, reference)?,
+                None => tiled.decode()?,
+            });
+        }
+
+        writer.append(timestamp, &frame).map_err(|e| format!("{}: {}", name, e))?;
+    }
+
+    Ok(())
+}
+
+// Function to compress an image in the legacy single block sequence format
+fn compress_legacy(
+    vcs_img_array: Array2<Vcs>,
+    width: usize,
+    height: usize,
+    quantizer: Quantizer,
+) -> Result<(), String> {
     // Compress the image using Discrete Cosine Transform
-    let compressed_vec = dct_on_block(vcs_img_array, height, width);
+    let layout = BlockLayout::LEGACY;
+    let compressed_vec = dct_on_block_scaled(vcs_img_array, height, width, None, quantizer, Curve::Linear, &layout);
     // For every Vcs value seen in 
     // the even image, they should be
     // compressed and stored in the vec
     
     // Convert compressed data to bytes
-    let compressed_img: Vec<[u8; 4]> = compressed_vec.clone().into_iter().map(u32::to_be_bytes).collect();
+    let compressed_img: Vec<[u8; 4]> = compressed_vec.into_iter().map(u32::to_be_bytes).collect();
     // For every data in compressed 
     // vec, they should be represented 
     // in bytes
 
     // Output the compressed data
-    output_rpeg_data(&compressed_img, width, height).unwrap();
-    
+    output_rpeg_data(&compressed_img, width, height).map_err(|e| e.to_string())
 }
 
-// Function to decompress an image
-pub fn decompress(filename: Option<&str>) {
-    // Read compressed image data
-    let (compressed_img, width, height) = input_rpeg_data(filename).unwrap();
+// Function to read the header and packed words of a legacy compressed image.
+// Bytes after the last whole word are ignored.
+pub fn read_legacy(bytes: &[u8]) -> Result<(Vec<u32>, usize, usize), String> {
+    let mut pos = 0;
+    if read_line(bytes, &mut pos)? != LEGACY_MAGIC {
+        return Err("not an rpeg file".to_string());
+    }
 
-    // Convert bytes back to compressed data
-    let compressed_vec: Vec<u32> = compressed_img.into_iter().map(u32::from_be_bytes).collect();
-    // For every byte seen in the rpeg
-    // they should be stored in the vec
-    // as compressed data
+    let (width, height) = match parse_numbers(read_line(bytes, &mut pos)?)?[..] {
+        [width, height] => (width, height),
+        _ => return Err("expected width and height".to_string()),
+    };
+    if !width.is_multiple_of(2) || !height.is_multiple_of(2) {
+        return Err("dimensions must be even".to_string());
+    }
+    check_dimensions(width, height)?;
+
+    let words = serial::words_from_bytes(&bytes[pos..], 32, ByteOrder::BigEndian).map_err(|e| e.to_string())?;
+    Ok((words, width, height))
+}
+
+// Function to decode a legacy compressed image held in memory.
+// Legacy files have no checksums, so the payload length is all we can check:
+// blocks missing from the end are painted grey and returned as damage.
+pub fn decode_legacy(bytes: &[u8]) -> Result<(Array2<Vcs>, Option<Damage>), String> {
+    let (mut compressed_vec, width, height) = read_legacy(bytes)?;
+
+    let damage = check_legacy_length(compressed_vec.len(), width, height);
+    compressed_vec.resize(width / 2 * (height / 2), 0);
 
     // Decompress the image using inverse Discrete Cosine Transform
-    let vcs_image = undo_dct_on_block(compressed_vec, height, width);
+    let mut vcs_image = undo_dct_on_block(compressed_vec, height, width);
     // For every compressed data seen in 
     // the rpeg file, they should be in 
     // Vcs format
 
-    // Convert YPbPr color space values back to RGB
-    let decompressed_rgb_array = vcs_to_rgb(&vcs_image);
-    // For every Vcs value seen in 
-    // the rpeg file, they should be inn
-    // Rgb format
+    if let Some(region) = &damage {
+        fill_grey(&mut vcs_image, region);
+    }
+    Ok((vcs_image, damage))
+}
 
-    // Convert the 2D array back to a 1D vector of RGB pixels
-    let mut decompressed_rgb_pixels = Vec::new();
+// Function to decompress an image, writing it to the output file or standard output.
+// With `recover` set, damaged regions are painted grey and
+// reported instead of stopping the decode.
+pub fn decompress(filename: Option<&str>, options: &DecompressOptions) -> Result<(), String> {
+    // Read the whole file, or standard input when there is none
+    let mut bytes = Vec::new();
+    match filename {
+        Some(name) => bytes = fs::read(name).map_err(|e| format!("{}: {}", name, e))?,
+        None => {
+            io::stdin().read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+        }
+    }
 
-    for y in 0..decompressed_rgb_array.height() {
-        for x in 0..decompressed_rgb_array.width() {
-            decompressed_rgb_pixels.push(decompressed_rgb_array.get(x, y).unwrap().clone());
+    // Tiled files carry their own header, so check for one first
+    if is_tiled(&bytes) {
+        return decompress_tiled(&bytes, options);
+    }
+    if is_sequence(&bytes) {
+        return Err(format!("{} is an image sequence, use rpeg extract", filename.unwrap_or("standard input")));
+    }
+
+    let (vcs_image, damage) = decode_legacy(&bytes)?;
+    if let Some(region) = damage {
+        if !options.recover {
+            return Err(region.reason);
         }
+        report(&[region]);
     }
-    
-    // Create an RGB image from the decompressed pixels
-    let decompressed_rgb_image = RgbImage {
-        pixels: decompressed_rgb_pixels,
-        width: width as u32,
-        height: height as u32,
-        denominator: 255,  
+
+    write_vcs_image(&vcs_image, None, Vec::new(), options.output.as_deref())
+}
+
+// Function to decompress a tiled image
+fn decompress_tiled(bytes: &[u8], options: &DecompressOptions) -> Result<(), String> {
+    // Read the header and tile index table
+    let tiled = read_tiled(bytes)?;
+    if tiled.delta {
+        return Err("residual frames can only be decoded from their sequence".to_string());
+    }
+
+    // Decompress every tile and put it back in place
+    let (vcs_image, damage) = tiled.decode_recovering();
+    // For every tile in the file, its
+    // pixels should be in Vcs format in
+    // the tile's spot in the image
+
+    write_decoded(DecodedFrame { tiled, image: vcs_image, damage }, options)
+}
+
+// Function to write a decoded tiled image along with its alpha plane and metadata.
+// Damage stops the decode unless `recover` is set, in which case it is reported.
+fn write_decoded(decoded: DecodedFrame, options: &DecompressOptions) -> Result<(), String> {
+    let recover = options.recover;
+    let DecodedFrame { tiled, image: vcs_image, damage } = decoded;
+
+    if let Some(region) = damage.first() {
+        if !recover {
+            return Err(region.reason.clone());
+        }
+        report(&damage);
+    }
+
+    // Damaged chunks only lose the data they carry
+    if let Some(reason) = &tiled.chunk_error {
+        if !recover {
+            return Err(reason.clone());
+        }
+        eprintln!("Damaged chunk: {}", reason);
+    }
+
+    // Decode the alpha plane if there is one
+    let alpha = match find_chunk(&tiled.chunks, &ALPHA_TAG) {
+        Some(chunk) => match decode_alpha(&chunk.data, tiled.width, tiled.height) {
+            Ok(alpha) => Some(alpha),
+            Err(e) if recover => {
+                eprintln!("Dropping the alpha channel: {}", e);
+                None
+            }
+            Err(e) => return Err(e),
+        },
+        None => None,
     };
-    // For every Rgb pixel seen,
-    // they should be store in the
-    // image file
 
-    // Write standard output
-    if let Err(e) = RgbImage::write(&decompressed_rgb_image, None) {
-        eprintln!("Failed to write image: {}", e);
+    // Comments go back into the image header, the rest only to the metadata directory
+    let metadata = Metadata::from_chunks(&tiled.chunks);
+    if let Some(dir) = &options.metadata_dir {
+        metadata.write_sidecars(Path::new(dir))?;
     }
 
+    write_vcs_image(&vcs_image, alpha, metadata.comments, options.output.as_deref())
+}
+
+// Function to decompress the frames of a sequence file.
+// A single frame is written like `-d` would write it, decoding forward from
+// the keyframe before it; otherwise every frame goes into the `options.output`
+// directory, named by its index and given its timestamp as the file's
+// modification time.
+pub fn extract(filename: &str, frame: Option<usize>, options: &DecompressOptions) -> Result<(), String> {
+    let file = File::open(filename).map_err(|e| format!("{}: {}", filename, e))?;
+    let mut sequence = Sequence::open(file)?;
+
+    if let Some(index) = frame {
+        return write_decoded(sequence.decode_frame(index)?, options);
+    }
+
+    let dir = options.output.as_deref().ok_or("extracting every frame needs an output directory")?;
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+
+    let mut previous: Option<Array2<Vcs>> = None;
+    for index in 0..sequence.len() {
+        let decoded = sequence.decode_next(index, previous.as_ref()).map_err(|e| format!("frame {}: {}", index, e))?;
+        let image = decoded.image.clone();
+
+        // Frames with alpha are written as PAM so the alpha plane survives
+        let has_alpha = find_chunk(&decoded.tiled.chunks, &ALPHA_TAG).is_some();
+        let path = Path::new(dir).join(format!("frame_{:05}.{}", index, if has_alpha { "pam" } else { "ppm" }));
+        let frame_options = DecompressOptions {
+            output: Some(path.to_string_lossy().into_owned()),
+            recover: options.recover,
+            // Each frame's sidecars go in a directory of their own
+            metadata_dir: options
+                .metadata_dir
+                .as_ref()
+                .map(|meta| Path::new(meta).join(format!("frame_{:05}", index)).to_string_lossy().into_owned()),
+        };
+        write_decoded(decoded, &frame_options).map_err(|e| format!("frame {}: {}", index, e))?;
+        previous = Some(image);
+
+        let modified = UNIX_EPOCH + Duration::from_millis(sequence.entries[index].timestamp);
+        File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(modified))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+
+    Ok(())
+}
+
+// Function to list the frames of a sequence file.
+// Returns None if the file is not a sequence.
+pub fn frames(filename: &str) -> Result<Option<Vec<FrameEntry>>, String> {
+    let mut file = File::open(filename).map_err(|e| format!("{}: {}", filename, e))?;
+    let mut magic = Vec::new();
+    (&mut file).take(sequence::SEQUENCE_MAGIC.len() as u64).read_to_end(&mut magic).map_err(|e| e.to_string())?;
+
+    if !is_sequence(&magic) {
+        return Ok(None);
+    }
+    Ok(Some(Sequence::open(file)?.entries))
+}
+
+// Function to check a compressed image for damage without decoding it.
+// Returns every damaged region found.
+pub fn verify(filename: &str) -> Result<Vec<Damage>, String> {
+    let bytes = fs::read(filename).map_err(|e| e.to_string())?;
+
+    if is_tiled(&bytes) {
+        let tiled = read_tiled(&bytes)?;
+        println!("{}: {} tiles with checksums", filename, tiled.entries.len());
+        return Ok(tiled.verify());
+    }
+
+    // Check every frame of a sequence, naming the frame in each report
+    if is_sequence(&bytes) {
+        let mut sequence = Sequence::open(io::Cursor::new(bytes))?;
+        println!("{}: sequence of {} frames", filename, sequence.len());
+
+        let mut damage = Vec::new();
+        for index in 0..sequence.len() {
+            let found = match sequence.frame(index) {
+                Ok(tiled) => tiled.verify(),
+                Err(reason) => {
+                    let entry = &sequence.entries[index];
+                    vec![Damage { x: 0, y: 0, width: entry.width, height: entry.height, reason }]
+                }
+            };
+            damage.extend(found.into_iter().map(|region| Damage {
+                reason: format!("frame {}: {}", index, region.reason),
+                ..region
+            }));
+        }
+        return Ok(damage);
+    }
+
+    let (compressed_vec, width, height) = read_legacy(&bytes)?;
+    println!("{}: legacy file without checksums, only the length can be checked", filename);
+    Ok(check_legacy_length(compressed_vec.len(), width, height).into_iter().collect())
+}
+
+// Function to read the header and packed words of a compressed image without decoding it
+pub fn info(filename: &str) -> Result<FileInfo, String> {
+    let bytes = fs::read(filename).map_err(|e| e.to_string())?;
+
+    if is_tiled(&bytes) {
+        let tiled = read_tiled(&bytes)?;
+
+        // Damaged tiles are left out of the histograms
+        let mut words = Vec::new();
+        for index in 0..tiled.entries.len() {
+            match tiled.tile_words(index) {
+                // Residual tiles start with a bitmap of the blocks they code
+                Ok(tile) if tiled.delta => {
+                    let rect = tiled.rect(index);
+                    words.extend(split_residual_tile(&tile, rect.width / 2 * (rect.height / 2)).1);
+                }
+                Ok(tile) => words.extend(tile),
+                Err(e) => eprintln!("Skipping tile {}: {}", index, e),
+            }
+        }
+
+        return Ok(FileInfo {
+            width: tiled.width,
+            height: tiled.height,
+            tiles: Some(tiled.entries.len()),
+            file_bytes: bytes.len(),
+            words,
+            // Residual words have a signed `a`
+            layout: if tiled.delta { tiled.layout.residual() } else { tiled.layout },
+            chunks: tiled.chunks,
+        });
+    }
+
+    let (compressed_vec, width, height) = read_legacy(&bytes)?;
+    Ok(FileInfo {
+        width,
+        height,
+        tiles: None,
+        file_bytes: bytes.len(),
+        words: compressed_vec.into_iter().map(u64::from).collect(),
+        layout: BlockLayout::LEGACY,
+        chunks: Vec::new(),
+    })
+}
+
+// Function to convert an array of Vcs values to an image.
+// The format is picked from the output filename, with a ppm (or a
+// pam when there is alpha) on standard output when there is none.
+fn write_vcs_image(
+    vcs_image: &Array2<Vcs>,
+    alpha: Option<Array2<u8>>,
+    comments: Vec<String>,
+    output: Option<&str>,
+) -> Result<(), String> {
+    // Convert YPbPr color space values back to RGB
+    let decompressed_rgb_array = vcs_to_rgb(vcs_image);
+    // For every Vcs value seen in 
+    // the rpeg file, they should be inn
+    // Rgb format
+
+    let image = Image { pixels: decompressed_rgb_array, alpha, comments };
+    save_image(&image, output).map_err(|e| format!("Failed to write image: {}", e))
 }
\ No newline at end of file
@@ -1,5 +1,9 @@
-use crate::block_packing::pack_block;
+use crate::block_packing::{pack_block, BlockLayout};
+use crate::adaptive::{scale_of_class, DEFAULT_CLASS};
+use crate::curve::Curve;
+use crate::rdo::Quantizer;
 use array2::Array2;
+use bitpack::word::Word;
 
 use crate::codec;
 use codec::Vcs;
@@ -24,27 +28,29 @@ pub fn transform_to_dct(block: &Array2<Vcs>) -> (f32, f32, f32, f32) {
     
 }
 
-/// Quantizes a single DCT coefficient
-fn quantize_coeff(val: f32) -> i64 {
-    if val.abs() > 0.3 {
-        if val > 0.0 { 15 } else { -15 }
-    } else {
-        (val * 50.0).round() as i64
-    }
-}
-
-/// Quantizes all DCT coefficients
-pub fn quantize_dct(a: f32, b: f32, c: f32, d: f32) -> (u64, i64, i64, i64) {
-    let a_quan = (a * 511.0).round() as u64;
-    let b_quan = quantize_coeff(b);
-    let c_quan = quantize_coeff(c);
-    let d_quan = quantize_coeff(d);
+/// Quantizes all DCT coefficients to the precision of `layout`, with b, c and d
+/// quantized on `curve` and their step multiplied by `scale`
+pub fn quantize_dct(
+    a: f32,
+    b: f32,
+    c: f32,
+    d: f32,
+    scale: f32,
+    curve: Curve,
+    layout: &BlockLayout,
+) -> (u64, i64, i64, i64) {
+    let max_level = layout.max_level();
+    let a_quan = (a * layout.a_scale()).round() as u64;
+    let b_quan = curve.quantize(b / scale, max_level);
+    let c_quan = curve.quantize(c / scale, max_level);
+    let d_quan = curve.quantize(d / scale, max_level);
 
     (a_quan, b_quan, c_quan, d_quan)
 }
 
-/// Computes the average of chroma values for a block of vcs pixels
-pub fn average_of_vcs(block: &Array2<Vcs>) -> (u64, u64) {
+/// Computes the average of chroma values for a block of vcs pixels,
+/// quantized to the chroma fields of `layout`
+pub fn average_of_vcs(block: &Array2<Vcs>, layout: &BlockLayout) -> (u64, u64) {
     let mut pbs = Vec::new();
     let mut prs = Vec::new();
 
@@ -56,19 +62,24 @@ pub fn average_of_vcs(block: &Array2<Vcs>) -> (u64, u64) {
         }
     }
 
-    let pb = csc411_arith::index_of_chroma(pbs.iter().sum::<f32>() / pbs.len() as f32);
-    let pr = csc411_arith::index_of_chroma(prs.iter().sum::<f32>() / prs.len() as f32);
-    (pb as u64, pr as u64)
+    let pb = layout.pb.chroma_index(pbs.iter().sum::<f32>() / pbs.len() as f32);
+    let pr = layout.pr.chroma_index(prs.iter().sum::<f32>() / prs.len() as f32);
+    (pb, pr)
 }
 
 /// Helper to calculates DCT coefficients and quantizes them for a block
-fn calculate_dct_and_quantize(block: &Array2<Vcs>) -> (u64, i64, i64, i64) {
-    let (dct_a, dct_b, dct_c, dct_d) = transform_to_dct(&block);
-    quantize_dct(dct_a, dct_b, dct_c, dct_d)
+fn calculate_dct_and_quantize(
+    block: &Array2<Vcs>,
+    scale: f32,
+    quantizer: Quantizer,
+    curve: Curve,
+    layout: &BlockLayout,
+) -> (u64, i64, i64, i64) {
+    quantizer.quantize(transform_to_dct(block), scale, curve, layout)
 }
 
 /// Helper to extracts a 2x2 block from the given image
-fn get_block(img: &Array2<Vcs>, x: usize, y: usize) -> Array2<Vcs> {
+pub fn get_block(img: &Array2<Vcs>, x: usize, y: usize) -> Array2<Vcs> {
     let block_data = Array2::from_row_major(2, 2, &vec![
         img.get(x, y).unwrap().clone(),
         img.get(x + 1, y).unwrap().clone(),
@@ -78,20 +89,37 @@ fn get_block(img: &Array2<Vcs>, x: usize, y: usize) -> Array2<Vcs> {
         block_data      
 }
 
-/// Performs DCT and quantization on blocks of an image
+/// Performs DCT and quantization on blocks of an image, packing them into 32-bit legacy words
 pub fn dct_on_block(img: Array2<Vcs>, height: usize, width: usize) -> Vec<u32>{
-    let mut temp_vec:Vec<u32> = Vec::new();
+    dct_on_block_scaled(img, height, width, None, Quantizer::Round, Curve::Linear, &BlockLayout::LEGACY)
+}
+
+/// Performs DCT and quantization on blocks of an image, quantizing each
+/// block with the scale of its class (one class per block, row-major),
+/// the given quantizer and the given curve, and packing it as `layout` says
+pub fn dct_on_block_scaled<W: Word>(
+    img: Array2<Vcs>,
+    height: usize,
+    width: usize,
+    classes: Option<&Array2<u8>>,
+    quantizer: Quantizer,
+    curve: Curve,
+    layout: &BlockLayout,
+) -> Vec<W>{
+    let mut temp_vec:Vec<W> = Vec::new();
 
     for y in (0..height).step_by(2) {
         for x in (0..width).step_by(2) {
             
             let block = get_block(&img, x, y);
 
-            let (a_q, b_q, c_q, d_q) = calculate_dct_and_quantize(&block);
+            let class = classes.map_or(DEFAULT_CLASS, |classes| *classes.get(x / 2, y / 2).unwrap());
+            let scale = scale_of_class(class);
+            let (a_q, b_q, c_q, d_q) = calculate_dct_and_quantize(&block, scale, quantizer, curve, layout);
 
-            let (pb_chroma, pr_chroma) = average_of_vcs(&block);
+            let (pb_chroma, pr_chroma) = average_of_vcs(&block, layout);
 
-            let packed_word = pack_block(a_q, b_q, c_q, d_q, pb_chroma, pr_chroma);
+            let packed_word = pack_block(layout, a_q, b_q, c_q, d_q, pb_chroma, pr_chroma).expect("quantized values fit the layout");
 
             
             temp_vec.push(packed_word);
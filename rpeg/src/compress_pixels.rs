@@ -7,7 +7,7 @@ use codec::{RgbFloat, Vcs};
 
 /// Adjusts the width of an image to ensure it is even
 pub fn even_width(img_array: Array2<Rgb>) -> usize{
-    if img_array.width() % 2 != 0 { 
+    if !img_array.width().is_multiple_of(2) { 
         img_array.width() - 1 
     } 
     else { 
@@ -17,7 +17,7 @@ pub fn even_width(img_array: Array2<Rgb>) -> usize{
 
 /// Adjusts the height of an image to ensure it is even
 pub fn even_height(img_array: Array2<Rgb>) -> usize{
-    if img_array.height() % 2 != 0 { 
+    if !img_array.height().is_multiple_of(2) { 
         img_array.height() - 1 
     } 
     else { 
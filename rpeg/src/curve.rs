@@ -0,0 +1,97 @@
+/// Tag of the chunk recording the quantization curve of b, c and d
+pub const CURVE_TAG: [u8; 4] = *b"QCRV";
+
+/// Largest magnitude the linear curve reaches before clamping
+const LINEAR_RANGE: f32 = 0.3;
+
+/// Largest magnitude the companded curves reach. b, c and d of a
+/// block with luma between 0 and 1 never exceed it.
+const COMPANDED_RANGE: f32 = 0.5;
+
+/// Magnitude below which the logarithmic curve is close to linear
+const LOG_KNEE: f32 = 0.02;
+
+// Enum listing the curves mapping b, c and d onto their levels.
+// `Linear` spends the levels evenly up to 0.3 and clamps beyond it, while
+// `Sqrt` and `Log` cover the whole range with finer steps near zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Curve {
+    #[default]
+    Linear,
+    Sqrt,
+    Log,
+}
+
+impl Curve {
+    /// Parses a curve name given on the command line
+    pub fn parse(name: &str) -> Result<Curve, String> {
+        match name {
+            "linear" => Ok(Curve::Linear),
+            "sqrt" => Ok(Curve::Sqrt),
+            "log" => Ok(Curve::Log),
+            _ => Err(format!("unknown curve `{}`, expected linear, sqrt or log", name)),
+        }
+    }
+
+    /// The name of the curve, as given on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Curve::Linear => "linear",
+            Curve::Sqrt => "sqrt",
+            Curve::Log => "log",
+        }
+    }
+
+    /// The chunk recording this curve
+    pub fn to_chunk_data(self) -> Vec<u8> {
+        let id = match self {
+            Curve::Linear => 0,
+            Curve::Sqrt => 1,
+            Curve::Log => 2,
+        };
+        vec![id]
+    }
+
+    /// The curve recorded by `to_chunk_data`
+    pub fn from_chunk_data(data: &[u8]) -> Result<Curve, String> {
+        match data {
+            [0] => Ok(Curve::Linear),
+            [1] => Ok(Curve::Sqrt),
+            [2] => Ok(Curve::Log),
+            [id] => Err(format!("unknown quantization curve {}", id)),
+            _ => Err("malformed quantization curve chunk".to_string()),
+        }
+    }
+
+    /// The level a coefficient magnitude falls on when the top level is
+    /// `max_level`, before rounding or clamping
+    pub fn level(self, magnitude: f32, max_level: i64) -> f32 {
+        let top = max_level as f32;
+        match self {
+            Curve::Linear => magnitude / LINEAR_RANGE * top,
+            Curve::Sqrt => top * (magnitude / COMPANDED_RANGE).sqrt(),
+            Curve::Log => top * (magnitude / LOG_KNEE).ln_1p() / (COMPANDED_RANGE / LOG_KNEE).ln_1p(),
+        }
+    }
+
+    /// The coefficient magnitude a level stands for when the top level is `max_level`
+    pub fn magnitude(self, level: i64, max_level: i64) -> f32 {
+        let fraction = level.abs() as f32 / max_level as f32;
+        match self {
+            Curve::Linear => level.abs() as f32 * LINEAR_RANGE / max_level as f32,
+            Curve::Sqrt => COMPANDED_RANGE * fraction * fraction,
+            Curve::Log => LOG_KNEE * (fraction * (COMPANDED_RANGE / LOG_KNEE).ln_1p()).exp_m1(),
+        }
+    }
+
+    /// Quantizes a b, c or d coefficient to the nearest of the levels up to `max_level`
+    pub fn quantize(self, val: f32, max_level: i64) -> i64 {
+        let magnitude = (self.level(val.abs(), max_level).round() as i64).min(max_level);
+        if val < 0.0 { -magnitude } else { magnitude }
+    }
+
+    /// Reverts the quantization of a b, c or d coefficient
+    pub fn dequantize(self, level: i64, max_level: i64) -> f32 {
+        self.magnitude(level, max_level).copysign(level as f32)
+    }
+}
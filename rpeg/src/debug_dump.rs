@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::Path;
+
+use array2::Array2;
+use csc411_image::Rgb;
+
+use crate::block_packing::LegacyBlock;
+use crate::codec;
+use codec::Vcs;
+
+/// Largest value written to a debug image
+const MAX_GRAY: f32 = 255.0;
+
+/// Largest magnitude of a quantized `b`, `c` or `d` coefficient
+const MAX_COEFF: f32 = 15.0;
+
+/// Largest quantized value of the `a` coefficient
+const MAX_A: f32 = 511.0;
+
+/// Converts a plane of values in 0..1 into the bytes of a binary PGM.
+/// csc411_image cannot write grayscale images, so the file is laid out here.
+fn plane_to_pgm(plane: &Array2<f32>) -> Vec<u8> {
+    let mut bytes = format!("P5\n{} {}\n{}\n", plane.width(), plane.height(), MAX_GRAY).into_bytes();
+    bytes.extend(plane.iter_row_major().map(|(_, _, &v)| (v.clamp(0.0, 1.0) * MAX_GRAY).round() as u8));
+    bytes
+}
+
+/// Builds a plane by applying `f` to every pixel of the Vcs image
+fn vcs_plane(img: &Array2<Vcs>, f: impl Fn(&Vcs) -> f32) -> Array2<f32> {
+    let data: Vec<f32> = img.iter_row_major().map(|(_, _, pixel)| f(pixel)).collect();
+    Array2::from_row_major(img.width(), img.height(), &data)
+}
+
+/// Builds the `a`, `b`, `c` and `d` coefficient planes, one value per 2x2 block,
+/// each scaled to 0..1
+pub fn coefficient_planes(words: &[u32], width: usize, height: usize) -> [Array2<f32>; 4] {
+    let mut planes: [Vec<f32>; 4] = Default::default();
+
+    for &word in words.iter().take(width / 2 * (height / 2)) {
+        let block = LegacyBlock::unpack(word);
+        planes[0].push(block.a as f32 / MAX_A);
+        for (plane, coeff) in planes[1..].iter_mut().zip([block.b, block.c, block.d]) {
+            plane.push((coeff as f32 + MAX_COEFF) / (2.0 * MAX_COEFF));
+        }
+    }
+
+    planes.map(|mut plane| {
+        plane.resize(width / 2 * (height / 2), 0.0);
+        Array2::from_row_major(width / 2, height / 2, &plane)
+    })
+}
+
+/// Builds a heat map of the per-pixel RGB distance between two images, scaled to 0..1
+pub fn error_plane(original: &Array2<Rgb>, decoded: &Array2<Rgb>) -> Array2<f32> {
+    let data: Vec<f32> = original
+        .iter_row_major()
+        .map(|(x, y, before)| {
+            let after = decoded.get(x, y).unwrap();
+            let dr = before.red as f32 - after.red as f32;
+            let dg = before.green as f32 - after.green as f32;
+            let db = before.blue as f32 - after.blue as f32;
+            (dr * dr + dg * dg + db * db).sqrt() / (3.0f32.sqrt() * MAX_GRAY)
+        })
+        .collect();
+
+    Array2::from_row_major(original.width(), original.height(), &data)
+}
+
+/// Writes the Y, Pb and Pr planes, the `a`/`b`/`c`/`d` coefficient planes and an error
+/// heat map of the decoded image against the original as PGMs in `dir`
+pub fn dump_planes(
+    dir: &Path,
+    vcs: &Array2<Vcs>,
+    words: &[u32],
+    original: &Array2<Rgb>,
+    decoded: &Array2<Rgb>,
+) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let [a, b, c, d] = coefficient_planes(words, vcs.width(), vcs.height());
+    let planes = [
+        ("y", vcs_plane(vcs, |pixel| pixel.y)),
+        ("pb", vcs_plane(vcs, |pixel| pixel.pb + 0.5)),
+        ("pr", vcs_plane(vcs, |pixel| pixel.pr + 0.5)),
+        ("a", a),
+        ("b", b),
+        ("c", c),
+        ("d", d),
+        ("error", error_plane(original, decoded)),
+    ];
+
+    for (name, plane) in planes {
+        let path = dir.join(format!("{}.pgm", name));
+        fs::write(&path, plane_to_pgm(&plane)).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,227 @@
+use array2::Array2;
+
+use crate::block_packing::{BlockLayout, Field};
+use crate::codec::Vcs;
+use crate::compress_dct::transform_to_dct;
+use crate::curve::Curve;
+use crate::integrity::Damage;
+use crate::rdo::Quantizer;
+use crate::tiling::{bitmap_words, extract_tile, parallel_map, paste_tile, tile_rects, TileRect, TiledImage};
+use crate::uncompress_dct::{undo_dct, undo_quantiza_dct};
+
+/// Keyframe interval used when none is given on the command line
+pub const DEFAULT_KEYFRAME_INTERVAL: usize = 30;
+
+/// Mean chroma residuals smaller than this do not stop a block from being skipped
+const CHROMA_SKIP: f32 = 0.01;
+
+/// Packs a block's quantized residual into a word.
+/// The fields are laid out by `layout`, except that `a` is signed.
+pub fn pack_residual(layout: &BlockLayout, a: i64, b: i64, c: i64, d: i64, pb: u64, pr: u64) -> u64 {
+    let values = [a, b, c, d, pb as i64, pr as i64];
+    layout
+        .residual()
+        .fields()
+        .into_iter()
+        .zip(values)
+        .fold(0, |word, (field, value)| field.put(word, value).unwrap())
+}
+
+/// Unpacks a word written by `pack_residual`
+pub fn unpack_residual(layout: &BlockLayout, word: u64) -> (i64, i64, i64, i64, u64, u64) {
+    let [a, b, c, d, pb, pr] = layout.residual().fields().map(|field| field.get(word).unwrap_or(0));
+    (a, b, c, d, pb as u64, pr as u64)
+}
+
+/// Subtracts the previous frame's 2x2 block at (x, y) from the current one
+fn residual_block(current: &Array2<Vcs>, previous: &Array2<Vcs>, x: usize, y: usize) -> Array2<Vcs> {
+    let mut data = Vec::with_capacity(4);
+
+    for (delta_x, delta_y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+        let now = current.get(x + delta_x, y + delta_y).unwrap();
+        let before = previous.get(x + delta_x, y + delta_y).unwrap();
+        data.push(Vcs { y: now.y - before.y, pb: now.pb - before.pb, pr: now.pr - before.pr });
+    }
+
+    Array2::from_row_major(2, 2, &data)
+}
+
+/// The chroma change a residual word's index stands for. The chroma table has
+/// no zero, so in residual words its index nearest zero means no change instead.
+fn residual_chroma(field: Field, index: u64) -> f32 {
+    if index == field.chroma_index(0.0) {
+        0.0
+    } else {
+        field.chroma_value(index)
+    }
+}
+
+/// True if a mean chroma residual is large enough to code and its nearest
+/// chroma level lands closer to it than leaving the channel alone would
+fn chroma_worth_coding(residual: f32, field: Field) -> bool {
+    let coded = residual_chroma(field, field.chroma_index(residual));
+    residual.abs() >= CHROMA_SKIP && (residual - coded).abs() < residual.abs()
+}
+
+/// Quantizes a residual block, or returns None if it is too small to be worth coding
+fn code_residual(block: &Array2<Vcs>, quantizer: Quantizer, curve: Curve, layout: &BlockLayout) -> Option<u64> {
+    let (a, b, c, d) = transform_to_dct(block);
+    let a_limit = layout.residual().a.max();
+    let a_q = ((a * layout.a_scale()).round() as i64).clamp(-a_limit, a_limit);
+    let (_, b_q, c_q, d_q) = quantizer.quantize((0.0, b, c, d), 1.0, curve, layout);
+
+    let (pb_sum, pr_sum) = block
+        .iter_row_major()
+        .fold((0.0, 0.0), |(pb, pr), (_, _, pixel)| (pb + pixel.pb, pr + pixel.pr));
+    let pb_changed = chroma_worth_coding(pb_sum / 4.0, layout.pb);
+    let pr_changed = chroma_worth_coding(pr_sum / 4.0, layout.pr);
+
+    if a_q == 0 && b_q == 0 && c_q == 0 && d_q == 0 && !pb_changed && !pr_changed {
+        return None;
+    }
+
+    // A channel not worth coding is left as it was
+    let chroma = |changed: bool, sum: f32, field: Field| field.chroma_index(if changed { sum / 4.0 } else { 0.0 });
+    let (pb, pr) = (chroma(pb_changed, pb_sum, layout.pb), chroma(pr_changed, pr_sum, layout.pr));
+    Some(pack_residual(layout, a_q, b_q, c_q, d_q, pb, pr))
+}
+
+/// Codes one tile of the current frame against the same tile of the previous frame.
+/// The tile starts with a bitmap holding one bit per block in row-major order, set
+/// for blocks that changed, followed by a residual word for each of those blocks.
+fn encode_residual_tile(
+    current: &Array2<Vcs>,
+    previous: &Array2<Vcs>,
+    quantizer: Quantizer,
+    curve: Curve,
+    layout: &BlockLayout,
+) -> Vec<u64> {
+    let blocks = current.width() / 2 * (current.height() / 2);
+    let mut bitmap = vec![0u64; bitmap_words(blocks)];
+    let mut coded = Vec::new();
+
+    let mut index = 0;
+    for y in (0..current.height()).step_by(2) {
+        for x in (0..current.width()).step_by(2) {
+            if let Some(word) = code_residual(&residual_block(current, previous, x, y), quantizer, curve, layout) {
+                bitmap[index / 32] |= 1 << (index % 32);
+                coded.push(word);
+            }
+            index += 1;
+        }
+    }
+
+    bitmap.extend(coded);
+    bitmap
+}
+
+/// Codes every tile of `current` as residuals against `previous`, which must
+/// be the decoder's reconstruction of the frame before it. The residuals of
+/// b, c and d are quantized on `curve` and packed as `layout` says.
+pub fn encode_delta(
+    current: &Array2<Vcs>,
+    previous: &Array2<Vcs>,
+    tile_size: usize,
+    quantizer: Quantizer,
+    curve: Curve,
+    layout: &BlockLayout,
+) -> Vec<Vec<u64>> {
+    let rects = tile_rects(current.width(), current.height(), tile_size);
+    parallel_map(&rects, |rect| {
+        let (current, previous) = (extract_tile(current, rect), extract_tile(previous, rect));
+        encode_residual_tile(&current, &previous, quantizer, curve, layout)
+    })
+}
+
+/// Splits a residual tile's words into the bitmap and the coded blocks
+pub fn split_residual_tile(words: &[u64], blocks: usize) -> (&[u64], &[u64]) {
+    words.split_at(bitmap_words(blocks).min(words.len()))
+}
+
+/// Adds one tile's residuals onto the same tile of the previous frame
+fn decode_residual_tile(
+    words: &[u64],
+    previous: &Array2<Vcs>,
+    rect: &TileRect,
+    curve: Curve,
+    layout: &BlockLayout,
+) -> Result<Array2<Vcs>, String> {
+    let blocks = rect.width / 2 * (rect.height / 2);
+    let (bitmap, mut coded) = split_residual_tile(words, blocks);
+
+    // Every set bit needs a word, and bits past the last block or above
+    // the 32 used in each bitmap word must be clear
+    let changed: usize = bitmap.iter().map(|word| word.count_ones() as usize).sum();
+    let stray_bits = bitmap.iter().any(|word| word >> 32 != 0)
+        || !blocks.is_multiple_of(32) && bitmap.last().is_some_and(|last| last >> (blocks % 32) != 0);
+    if changed != coded.len() || stray_bits {
+        return Err("residual bitmap does not match the coded blocks".to_string());
+    }
+
+    let mut tile = extract_tile(previous, rect);
+    let mut index = 0;
+    for y in (0..rect.height).step_by(2) {
+        for x in (0..rect.width).step_by(2) {
+            if bitmap[index / 32] & (1 << (index % 32)) != 0 {
+                let (a, b, c, d, pb, pr) = unpack_residual(layout, coded[0]);
+                coded = &coded[1..];
+
+                let (_, b, c, d) = undo_quantiza_dct(0, b, c, d, 1.0, curve, layout);
+                let block = undo_dct(a as f32 / layout.a_scale(), b, c, d);
+                let (pb, pr) = (residual_chroma(layout.pb, pb), residual_chroma(layout.pr, pr));
+
+                for (delta_x, delta_y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let mut pixel = tile.get(x + delta_x, y + delta_y).unwrap().clone();
+                    pixel.y += block.get(delta_x, delta_y).unwrap().y;
+                    pixel.pb += pb;
+                    pixel.pr += pr;
+                    tile.set(x + delta_x, y + delta_y, pixel);
+                }
+            }
+            index += 1;
+        }
+    }
+
+    Ok(tile)
+}
+
+/// Decodes a residual frame on top of the previous frame's reconstruction.
+/// Tiles that cannot be decoded keep the previous frame's pixels and are
+/// returned as damaged regions.
+pub fn decode_delta_recovering(
+    tiled: &TiledImage,
+    previous: &Array2<Vcs>,
+) -> Result<(Array2<Vcs>, Vec<Damage>), String> {
+    if (previous.width(), previous.height()) != (tiled.width, tiled.height) {
+        return Err("residual frame does not match the size of the frame before it".to_string());
+    }
+
+    let rects = tiled.rects();
+    let indices: Vec<usize> = (0..rects.len()).collect();
+    let decoded = parallel_map(&indices, |&index| {
+        tiled
+            .tile_words(index)
+            .and_then(|words| decode_residual_tile(&words, previous, &rects[index], tiled.curve, &tiled.layout))
+    });
+
+    let mut img = previous.clone();
+    let mut damage = Vec::new();
+    for (index, (rect, tile)) in rects.iter().zip(decoded).enumerate() {
+        match tile {
+            Ok(tile) => paste_tile(&mut img, rect, &tile),
+            Err(e) => damage.push(tiled.damage(index, e)),
+        }
+    }
+
+    Ok((img, damage))
+}
+
+/// Decodes a residual frame on top of the previous frame's reconstruction.
+/// Fails on the first tile that cannot be decoded.
+pub fn decode_delta(tiled: &TiledImage, previous: &Array2<Vcs>) -> Result<Array2<Vcs>, String> {
+    let (img, damage) = decode_delta_recovering(tiled, previous)?;
+    match damage.first() {
+        Some(region) => Err(region.reason.clone()),
+        None => Ok(img),
+    }
+}
@@ -0,0 +1,258 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read};
+use std::path::Path;
+
+use array2::Array2;
+use csc411_image::{Rgb, RgbImage, Write};
+
+use crate::metadata::header_comments;
+use crate::pam::{is_pam, is_ppm, read_pam, read_ppm, write_pam, write_raw_ppm};
+
+/// Largest channel value of the images handed to the codec
+const DENOMINATOR: u16 = 255;
+
+// Enum listing the image formats rpeg can read and write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Ppm,
+    Pam,
+    Png,
+    Bmp,
+    Tga,
+    Farbfeld,
+}
+
+impl Format {
+    /// Picks a format from a file's extension, defaulting to PPM
+    pub fn from_extension(filename: &str) -> Format {
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("pam") => Format::Pam,
+            Some("png") => Format::Png,
+            Some("bmp") => Format::Bmp,
+            Some("tga") => Format::Tga,
+            Some("ff") | Some("farbfeld") => Format::Farbfeld,
+            _ => Format::Ppm,
+        }
+    }
+
+    /// True if the file's extension names a format rpeg was built to read
+    pub fn has_image_extension(filename: &str) -> bool {
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        matches!(extension.as_deref(), Some("ppm" | "pam" | "png" | "bmp" | "tga" | "ff" | "farbfeld"))
+            && Format::from_extension(filename).enabled()
+    }
+
+    /// Picks a format from the first bytes of a file.
+    /// TGA has no signature, so it falls back to the extension.
+    pub fn detect(bytes: &[u8], filename: &str) -> Format {
+        if is_pam(bytes) {
+            Format::Pam
+        } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Format::Png
+        } else if bytes.starts_with(b"BM") {
+            Format::Bmp
+        } else if bytes.starts_with(b"farbfeld") {
+            Format::Farbfeld
+        } else if is_ppm(bytes) {
+            Format::Ppm
+        } else {
+            Format::from_extension(filename)
+        }
+    }
+
+    /// The name of the cargo feature that enables this format
+    pub fn feature(self) -> &'static str {
+        match self {
+            Format::Ppm => "ppm",
+            Format::Pam => "pam",
+            Format::Png => "png",
+            Format::Bmp => "bmp",
+            Format::Tga => "tga",
+            Format::Farbfeld => "farbfeld",
+        }
+    }
+
+    /// Whether support for this format was compiled in
+    pub fn enabled(self) -> bool {
+        match self {
+            Format::Ppm | Format::Pam => true,
+            Format::Png => cfg!(feature = "png"),
+            Format::Bmp => cfg!(feature = "bmp"),
+            Format::Tga => cfg!(feature = "tga"),
+            Format::Farbfeld => cfg!(feature = "farbfeld"),
+        }
+    }
+
+    /// Fails with a helpful message if this format was not compiled in
+    fn check_enabled(self) -> Result<(), String> {
+        if self.enabled() {
+            Ok(())
+        } else {
+            Err(self.missing())
+        }
+    }
+
+    /// The error reported when this format was not compiled in
+    fn missing(self) -> String {
+        format!("rpeg was built without the `{}` feature", self.feature())
+    }
+}
+
+// Struct holding a decoded image along with what travels with its pixels
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub pixels: Array2<Rgb>,
+    pub alpha: Option<Array2<u8>>,
+    pub comments: Vec<String>,
+}
+
+/// Reads an image in any supported format, along with its alpha plane and header
+/// comments if it has them. Only PAM files carry alpha, and only PPM and PAM files
+/// carry comments. Standard input is read when there is no filename, and its format
+/// is picked from its first bytes, defaulting to PPM.
+pub fn load_image(filename: Option<&str>) -> Result<Image, String> {
+    // Read the whole file, or standard input when there is none
+    let mut bytes = Vec::new();
+    match filename {
+        Some(name) => bytes = fs::read(name).map_err(|e| format!("{}: {}", name, e))?,
+        None => {
+            io::stdin().read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+        }
+    }
+
+    let format = Format::detect(&bytes, filename.unwrap_or(""));
+    format.check_enabled()?;
+
+    let (pixels, alpha) = match format {
+        Format::Ppm => (read_ppm(&bytes)?, None),
+        Format::Pam => read_pam(&bytes)?,
+        _ => (decode(&bytes, format)?, None),
+    };
+    let comments = match format {
+        Format::Ppm | Format::Pam => header_comments(&bytes),
+        _ => Vec::new(),
+    };
+
+    Ok(Image { pixels, alpha, comments })
+}
+
+/// Writes an image in the format picked by the output filename's extension.
+/// An image with alpha is written as PAM to standard output, and its alpha is
+/// dropped with a warning if the output format cannot hold it. Comments are
+/// kept in PPM and PAM headers.
+pub fn save_image(image: &Image, filename: Option<&str>) -> Result<(), String> {
+    save(&image.pixels, image.alpha.as_ref(), &image.comments, filename)
+}
+
+/// Writes the parts of an image, see `save_image`
+fn save(img: &Array2<Rgb>, alpha: Option<&Array2<u8>>, comments: &[String], filename: Option<&str>) -> Result<(), String> {
+    let format = match (filename, alpha) {
+        (Some(name), _) => Format::from_extension(name),
+        (None, Some(_)) => Format::Pam,
+        (None, None) => Format::Ppm,
+    };
+    format.check_enabled()?;
+
+    if alpha.is_some() && format != Format::Pam {
+        eprintln!("Dropping the alpha channel, which {} output cannot hold", format.feature());
+    }
+
+    // Output to a file, or standard output when there is none
+    let netpbm = |write: &dyn Fn(&mut dyn io::Write) -> io::Result<()>| -> Result<(), String> {
+        match filename {
+            Some(name) => File::create(name).and_then(|file| write(&mut BufWriter::new(file))),
+            None => write(&mut BufWriter::new(io::stdout().lock())),
+        }
+        .map_err(|e| e.to_string())
+    };
+
+    match format {
+        Format::Ppm if comments.is_empty() => write_ppm(img, filename),
+        Format::Ppm => netpbm(&|out| write_raw_ppm(out, img, comments)),
+        Format::Pam => netpbm(&|out| write_pam(out, img, alpha, comments)),
+        _ => encode(img, filename.unwrap(), format),
+    }
+}
+
+/// Writes a PPM through csc411_image
+fn write_ppm(img: &Array2<Rgb>, filename: Option<&str>) -> Result<(), String> {
+    let pixels = img.iter_row_major().map(|(_, _, pixel)| pixel.clone()).collect();
+    let rgb_image = RgbImage {
+        pixels,
+        width: img.width() as u32,
+        height: img.height() as u32,
+        denominator: DENOMINATOR,
+    };
+    rgb_image.write(filename).map_err(|e| e.to_string())
+}
+
+#[cfg(any(feature = "png", feature = "bmp", feature = "tga", feature = "farbfeld"))]
+impl Format {
+    /// The matching format in the image crate
+    fn image_format(self) -> image::ImageFormat {
+        match self {
+            Format::Ppm | Format::Pam => image::ImageFormat::Pnm,
+            Format::Png => image::ImageFormat::Png,
+            Format::Bmp => image::ImageFormat::Bmp,
+            Format::Tga => image::ImageFormat::Tga,
+            Format::Farbfeld => image::ImageFormat::Farbfeld,
+        }
+    }
+}
+
+/// Decodes a non-PPM image with the image crate, reducing it to 8 bits per channel
+#[cfg(any(feature = "png", feature = "bmp", feature = "tga", feature = "farbfeld"))]
+fn decode(bytes: &[u8], format: Format) -> Result<Array2<Rgb>, String> {
+    let img = image::load_from_memory_with_format(bytes, format.image_format())
+        .map_err(|e| e.to_string())?
+        .to_rgb8();
+
+    let pixels: Vec<Rgb> = img
+        .pixels()
+        .map(|p| Rgb { red: p[0] as u16, green: p[1] as u16, blue: p[2] as u16 })
+        .collect();
+
+    Ok(Array2::from_row_major(img.width() as usize, img.height() as usize, &pixels))
+}
+
+/// Encodes a non-PPM image with the image crate
+#[cfg(any(feature = "png", feature = "bmp", feature = "tga", feature = "farbfeld"))]
+fn encode(img: &Array2<Rgb>, filename: &str, format: Format) -> Result<(), String> {
+    let bytes: Vec<u8> = img
+        .iter_row_major()
+        .flat_map(|(_, _, p)| [p.red, p.green, p.blue])
+        .map(|channel| channel.min(DENOMINATOR) as u8)
+        .collect();
+
+    let rgb = image::RgbImage::from_raw(img.width() as u32, img.height() as u32, bytes)
+        .ok_or("image dimensions do not match its pixels")?;
+
+    // Farbfeld only stores 16-bit RGBA
+    let encoded = match format {
+        Format::Farbfeld => image::DynamicImage::ImageRgba16(image::DynamicImage::ImageRgb8(rgb).to_rgba16()),
+        _ => image::DynamicImage::ImageRgb8(rgb),
+    };
+
+    encoded
+        .save_with_format(filename, format.image_format())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(any(feature = "png", feature = "bmp", feature = "tga", feature = "farbfeld")))]
+fn decode(_bytes: &[u8], format: Format) -> Result<Array2<Rgb>, String> {
+    Err(format.missing())
+}
+
+#[cfg(not(any(feature = "png", feature = "bmp", feature = "tga", feature = "farbfeld")))]
+fn encode(_img: &Array2<Rgb>, _filename: &str, format: Format) -> Result<(), String> {
+    Err(format.missing())
+}
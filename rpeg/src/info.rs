@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+
+use crate::block_packing::BlockLayout;
+use crate::chunks::Chunk;
+use crate::metadata::{describe_chunk, format_timestamp};
+use crate::sequence::FrameEntry;
+
+/// Names of the fields in a packed block word, in packing order
+pub const FIELD_NAMES: [&str; 6] = ["a", "b", "c", "d", "pb", "pr"];
+
+/// Bytes per pixel of the uncompressed RGB image
+const RAW_BYTES_PER_PIXEL: usize = 3;
+
+/// Values of `a` are grouped into this many buckets when printed
+const A_BUCKETS: i64 = 16;
+
+/// Width of the longest bar in a printed histogram
+const BAR_WIDTH: usize = 40;
+
+// Struct summarising the contents of a compressed image
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileInfo {
+    pub width: usize,
+    pub height: usize,
+    pub tiles: Option<usize>,
+    pub file_bytes: usize,
+    pub words: Vec<u64>,
+    pub layout: BlockLayout,
+    pub chunks: Vec<Chunk>,
+}
+
+// Struct counting how often each value of a field occurs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Histogram {
+    pub counts: BTreeMap<i64, usize>,
+}
+
+impl Histogram {
+    /// Records one occurrence of `value`
+    pub fn add(&mut self, value: i64) {
+        *self.counts.entry(value).or_insert(0) += 1;
+    }
+
+    /// Groups values into buckets of `size`, keyed by the bucket's lowest value
+    pub fn bucketed(&self, size: i64) -> Histogram {
+        let mut buckets = Histogram::default();
+        for (&value, &count) in &self.counts {
+            *buckets.counts.entry(value.div_euclid(size) * size).or_insert(0) += count;
+        }
+        buckets
+    }
+}
+
+/// Counts the values of every field across all packed words.
+/// The histograms are returned in the order of `FIELD_NAMES`, along with
+/// the number of words that could not be unpacked and were left out.
+pub fn field_histograms(words: &[u64], layout: &BlockLayout) -> (Vec<Histogram>, usize) {
+    let mut histograms = vec![Histogram::default(); FIELD_NAMES.len()];
+    let mut skipped = 0;
+
+    for &word in words {
+        // The fields are read as the layout says, so a residual `a` keeps its sign
+        let fields = layout.fields().map(|field| field.get(word));
+        if fields.contains(&None) {
+            skipped += 1;
+            continue;
+        }
+        for (histogram, value) in histograms.iter_mut().zip(fields.into_iter().flatten()) {
+            histogram.add(value);
+        }
+    }
+
+    (histograms, skipped)
+}
+
+impl FileInfo {
+    /// Number of 2x2 blocks covering the image
+    pub fn block_count(&self) -> usize {
+        self.width / 2 * (self.height / 2)
+    }
+
+    /// Size of the buckets `a` is grouped into, which cover its range in `A_BUCKETS` steps
+    pub fn a_bucket(&self) -> i64 {
+        (self.layout.a.max() + 1) / A_BUCKETS
+    }
+
+    /// Size of the packed words in bytes
+    pub fn payload_bytes(&self) -> usize {
+        self.words.len() * self.layout.word_bytes()
+    }
+
+    /// Compressed bits spent on each pixel, counting the whole file
+    pub fn bits_per_pixel(&self) -> f64 {
+        (self.file_bytes * 8) as f64 / (self.width * self.height).max(1) as f64
+    }
+
+    /// Size of the raw RGB image divided by the size of the file
+    pub fn compression_ratio(&self) -> f64 {
+        (self.width * self.height * RAW_BYTES_PER_PIXEL) as f64 / self.file_bytes.max(1) as f64
+    }
+
+    /// Prints the summary and field histograms to standard output
+    pub fn print(&self) {
+        println!("dimensions:        {}x{}", self.width, self.height);
+        if let Some(tiles) = self.tiles {
+            println!("tiles:             {}", tiles);
+        }
+        println!("blocks:            {}", self.block_count());
+        println!("layout:            {} ({}-bit words)", self.layout.name, self.layout.word_bits);
+        println!("payload size:      {} bytes", self.payload_bytes());
+        println!("file size:         {} bytes", self.file_bytes);
+        println!("bits per pixel:    {:.3}", self.bits_per_pixel());
+        println!("compression ratio: {:.2}:1", self.compression_ratio());
+
+        let (histograms, skipped) = field_histograms(&self.words, &self.layout);
+        if skipped > 0 {
+            println!("undecodable words: {} (left out of the histograms)", skipped);
+        }
+
+        if !self.chunks.is_empty() {
+            println!();
+            println!("chunks:");
+            for chunk in &self.chunks {
+                println!("  {}", describe_chunk(chunk));
+            }
+        }
+
+        for (name, histogram) in FIELD_NAMES.iter().zip(histograms) {
+            let histogram = if *name == "a" { histogram.bucketed(self.a_bucket()) } else { histogram };
+            println!();
+            println!("{}:", name);
+            print_histogram(&histogram);
+        }
+    }
+}
+
+/// Prints one line per frame of a sequence with its timestamp, dimensions and size
+pub fn print_frames(entries: &[FrameEntry]) {
+    println!("frames:            {}", entries.len());
+    println!();
+    println!("{:>6} {:>5} {:>27} {:>11} {:>10}", "frame", "kind", "timestamp", "dimensions", "bytes");
+
+    for (index, entry) in entries.iter().enumerate() {
+        let timestamp = format_timestamp(entry.timestamp / 1000);
+        // Put the milliseconds in front of the time zone
+        let timestamp = timestamp.replace(" UTC", &format!(".{:03} UTC", entry.timestamp % 1000));
+        let dimensions = format!("{}x{}", entry.width, entry.height);
+        let kind = if entry.delta { "delta" } else { "key" };
+        println!("{:>6} {:>5} {:>27} {:>11} {:>10}", index, kind, timestamp, dimensions, entry.len);
+    }
+}
+
+/// Prints one line per value with a bar scaled to the most common value
+fn print_histogram(histogram: &Histogram) {
+    let max = histogram.counts.values().copied().max().unwrap_or(0).max(1);
+
+    for (value, count) in &histogram.counts {
+        let bar = "#".repeat((count * BAR_WIDTH).div_ceil(max));
+        println!("{:>6} {:>10} {}", value, count, bar);
+    }
+}
@@ -0,0 +1,88 @@
+use array2::Array2;
+
+use crate::codec;
+use codec::Vcs;
+
+/// Colour painted over regions that could not be decoded
+pub const GREY: Vcs = Vcs { y: 0.5, pb: 0.0, pr: 0.0 };
+
+/// Largest number of pixels a decoded image may have, so a damaged
+/// header cannot ask for an enormous allocation
+pub const MAX_PIXELS: usize = 1 << 28;
+
+/// Reversed CRC-32 (IEEE 802.3) polynomial
+const CRC32_POLY: u32 = 0xEDB8_8320;
+
+// Struct describing a region of the image that failed to decode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Damage {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub reason: String,
+}
+
+/// Computes the CRC-32 checksum of a byte slice
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (CRC32_POLY & mask);
+        }
+    }
+
+    !crc
+}
+
+/// Checks that an image of the given dimensions is small enough to decode
+pub fn check_dimensions(width: usize, height: usize) -> Result<(), String> {
+    match width.checked_mul(height) {
+        Some(pixels) if pixels <= MAX_PIXELS => Ok(()),
+        _ => Err(format!("image of {}x{} pixels is too large to decode", width, height)),
+    }
+}
+
+/// Paints a damaged region of the image grey
+pub fn fill_grey(img: &mut Array2<Vcs>, damage: &Damage) {
+    for y in damage.y..damage.y + damage.height {
+        for x in damage.x..damage.x + damage.width {
+            img.set(x, y, GREY);
+        }
+    }
+}
+
+/// Prints every damaged region to standard error
+pub fn report(damage: &[Damage]) {
+    for region in damage {
+        eprintln!(
+            "Damaged region at ({}, {}) size {}x{}: {}",
+            region.x, region.y, region.width, region.height, region.reason
+        );
+    }
+}
+
+/// Checks that a legacy payload holds one word per 2x2 block.
+/// Returns the region of the image whose blocks are missing, if any.
+pub fn check_legacy_length(words: usize, width: usize, height: usize) -> Option<Damage> {
+    let blocks_per_row = width / 2;
+    let expected = blocks_per_row * (height / 2);
+
+    if words >= expected {
+        return None;
+    }
+
+    // Blocks are stored in row-major order, so damage starts at the first
+    // block row that is not complete and runs to the bottom of the image
+    let first_row = words / blocks_per_row.max(1) * 2;
+    Some(Damage {
+        x: 0,
+        y: first_row,
+        width,
+        height: height - first_row,
+        reason: format!("payload holds {} of {} blocks", words, expected),
+    })
+}
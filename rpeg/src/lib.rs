@@ -3,4 +3,18 @@ pub mod compress_pixels;
 pub mod compress_dct;
 pub mod uncompress_dct;
 pub mod uncompress_pixels;
-pub mod block_packing;
\ No newline at end of file
+pub mod block_packing;
+pub mod tiling;
+pub mod integrity;
+pub mod info;
+pub mod debug_dump;
+pub mod formats;
+pub mod pam;
+pub mod alpha;
+pub mod chunks;
+pub mod metadata;
+pub mod sequence;
+pub mod delta;
+pub mod adaptive;
+pub mod rdo;
+pub mod curve;
@@ -1,15 +1,191 @@
 use std::env;
-use rpeg::codec::{compress, decompress};
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let argnum = args.len();
-    assert!(argnum == 2 || argnum == 3);
-    let filename = args.iter().nth(2).unwrap();
-    match args[1].as_str() {
-        "-c" => compress(Some(filename)),
-        "-d" => decompress(Some(filename)),
-        _ => {
-            eprintln!("Usage: rpeg -d [filename]\nrpeg -c [filename]")
+use std::process;
+use rpeg::alpha::AlphaMode;
+use rpeg::block_packing::BlockLayout;
+use rpeg::codec::{
+    compress, compress_with_dump, decompress, extract, frames, info, pack, verify, CompressOptions, DecompressOptions,
+};
+use rpeg::delta::DEFAULT_KEYFRAME_INTERVAL;
+use rpeg::info::print_frames;
+use rpeg::integrity::report;
+use rpeg::metadata::Metadata;
+use rpeg::curve::Curve;
+use rpeg::rdo::Quantizer;
+use rpeg::tiling::DEFAULT_TILE_SIZE;
+
+const USAGE: &str = "Usage: rpeg -c [--tile[=size]] [--adaptive] [--alpha lossless|quantized] [--dump directory]
+        [--rdo lambda] [--curve linear|sqrt|log] [--layout legacy|high|near-lossless]
+        [--comment text] [--icc profile] [--exif file] [filename]
+rpeg -d [--recover] [-o output] [--metadata directory] [filename]
+rpeg verify [filename]
+rpeg info [filename]
+rpeg pack [--append] [--delta[=interval]] [--tile[=size]] [--adaptive] [--rdo lambda]
+        [--curve linear|sqrt|log] [--layout legacy|high|near-lossless] [-o output] directory
+rpeg extract [--frame index] [--recover] [--metadata directory] -o output filename
+
+Input images may be PPM, PNG, BMP, TGA or farbfeld, depending on the
+features rpeg was built with, or PAM with an alpha channel. The -o output
+format follows its extension. pack compresses every image in a directory
+into one sequence file; extract writes the frames back into the -o
+directory, or a single frame to -o or standard output.";
+
+// Struct holding the options given after the command
+#[derive(Debug, Default)]
+struct Flags {
+    tile: Option<usize>,
+    adaptive: bool,
+    quantizer: Quantizer,
+    curve: Curve,
+    layout: BlockLayout,
+    alpha: AlphaMode,
+    dump: Option<String>,
+    output: Option<String>,
+    recover: bool,
+    metadata: Metadata,
+    metadata_dir: Option<String>,
+    append: bool,
+    delta: Option<usize>,
+    frame: Option<usize>,
+    filename: Option<String>,
+}
+
+/// Reads a file given as an option's value
+fn read_file(filename: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(filename).map_err(|e| format!("{}: {}", filename, e))
+}
+
+/// Parses the options and filename that follow the command
+fn parse_flags(args: &[String]) -> Result<Flags, String> {
+    let mut flags = Flags::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tile" => flags.tile = Some(DEFAULT_TILE_SIZE),
+            // The size is joined to the flag so a numeric filename is never taken for it
+            _ if arg.starts_with("--tile=") => {
+                let size = &arg["--tile=".len()..];
+                flags.tile = Some(size.parse().map_err(|_| format!("bad tile size `{}`", size))?);
+            }
+            "--adaptive" => flags.adaptive = true,
+            "--rdo" => flags.quantizer = Quantizer::parse(args.next().ok_or(USAGE)?)?,
+            "--curve" => flags.curve = Curve::parse(args.next().ok_or(USAGE)?)?,
+            "--layout" => flags.layout = BlockLayout::parse(args.next().ok_or(USAGE)?)?,
+            "--alpha" => flags.alpha = AlphaMode::parse(args.next().ok_or(USAGE)?)?,
+            "--dump" => flags.dump = Some(args.next().ok_or(USAGE)?.clone()),
+            "-o" => flags.output = Some(args.next().ok_or(USAGE)?.clone()),
+            "--recover" => flags.recover = true,
+            "--comment" => flags.metadata.comments.push(args.next().ok_or(USAGE)?.clone()),
+            "--icc" => flags.metadata.icc_profile = Some(read_file(args.next().ok_or(USAGE)?)?),
+            "--exif" => flags.metadata.exif = Some(read_file(args.next().ok_or(USAGE)?)?),
+            "--metadata" => flags.metadata_dir = Some(args.next().ok_or(USAGE)?.clone()),
+            "--append" => flags.append = true,
+            "--delta" => flags.delta = Some(DEFAULT_KEYFRAME_INTERVAL),
+            _ if arg.starts_with("--delta=") => {
+                let interval = &arg["--delta=".len()..];
+                flags.delta = Some(interval.parse().map_err(|_| format!("bad keyframe interval `{}`", interval))?);
+            }
+            "--frame" => {
+                let index = args.next().ok_or(USAGE)?;
+                flags.frame = Some(index.parse().map_err(|_| format!("bad frame index `{}`", index))?);
+            }
+            _ if flags.filename.is_none() => flags.filename = Some(arg.clone()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    Ok(flags)
+}
+
+/// Runs the command named by the first argument
+fn run(args: &[String]) -> Result<(), String> {
+    let (command, rest) = args.split_first().ok_or(USAGE)?;
+    let mut flags = parse_flags(rest)?;
+    let filename = flags.filename.as_deref();
+
+    match command.as_str() {
+        "-c" => match (flags.tile, &flags.dump) {
+            (Some(_), Some(_)) => Err("--tile and --dump cannot be combined".to_string()),
+            (None, Some(_)) if flags.adaptive => Err("--adaptive and --dump cannot be combined".to_string()),
+            (None, Some(_)) if flags.curve != Curve::Linear => Err("--curve and --dump cannot be combined".to_string()),
+            (None, Some(_)) if flags.layout != BlockLayout::LEGACY => {
+                Err("--layout and --dump cannot be combined".to_string())
+            }
+            (None, Some(_)) if !flags.metadata.is_empty() => {
+                Err("--comment, --icc and --exif cannot be combined with --dump".to_string())
+            }
+            (None, Some(dir)) => compress_with_dump(filename, dir, flags.quantizer),
+            (tile_size, None) => {
+                let options = CompressOptions {
+                    tile_size,
+                    alpha_mode: flags.alpha,
+                    metadata: std::mem::take(&mut flags.metadata),
+                    adaptive: flags.adaptive,
+                    quantizer: flags.quantizer,
+                    curve: flags.curve,
+                    layout: flags.layout,
+                    ..Default::default()
+                };
+                compress(filename, &options)
+            }
+        },
+        "-d" | "extract" => {
+            let options = DecompressOptions {
+                output: flags.output.clone(),
+                recover: flags.recover,
+                metadata_dir: flags.metadata_dir.clone(),
+            };
+            if command == "extract" {
+                extract(filename.ok_or(USAGE)?, flags.frame, &options)
+            } else {
+                decompress(filename, &options)
+            }
+        }
+        "pack" => {
+            let metadata = std::mem::take(&mut flags.metadata);
+            if flags.delta == Some(0) {
+                return Err("the keyframe interval must be at least 1".to_string());
+            }
+            let options = CompressOptions {
+                tile_size: flags.tile,
+                alpha_mode: flags.alpha,
+                metadata,
+                keyframe_interval: flags.delta,
+                adaptive: flags.adaptive,
+                quantizer: flags.quantizer,
+                curve: flags.curve,
+                layout: flags.layout,
+            };
+            pack(filename.ok_or(USAGE)?, flags.output.as_deref(), flags.append, &options)
         }
+        "verify" => {
+            let filename = filename.ok_or(USAGE)?;
+            let damage = verify(filename)?;
+            report(&damage);
+            if damage.is_empty() {
+                println!("{}: ok", filename);
+                Ok(())
+            } else {
+                Err(format!("{}: {} damaged regions", filename, damage.len()))
+            }
+        }
+        "info" => {
+            let filename = filename.ok_or(USAGE)?;
+            match frames(filename)? {
+                Some(entries) => print_frames(&entries),
+                None => info(filename)?.print(),
+            }
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        process::exit(1);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,190 @@
+use std::fs;
+use std::path::Path;
+
+use crate::adaptive::SCALE_TAG;
+use crate::curve::{Curve, CURVE_TAG};
+use crate::chunks::{Chunk, ALPHA_TAG};
+
+/// Tag of a chunk holding one text comment
+pub const TEXT_TAG: [u8; 4] = *b"TEXT";
+
+/// Tag of the chunk holding an ICC colour profile
+pub const ICC_TAG: [u8; 4] = *b"ICCP";
+
+/// Tag of the chunk holding an EXIF blob
+pub const EXIF_TAG: [u8; 4] = *b"EXIF";
+
+/// Tag of the chunk holding the creation time in seconds since the Unix epoch
+pub const TIME_TAG: [u8; 4] = *b"TIME";
+
+/// Seconds in a day
+const DAY: u64 = 86_400;
+
+// Struct holding the metadata carried alongside an image
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub comments: Vec<String>,
+    pub icc_profile: Option<Vec<u8>>,
+    pub exif: Option<Vec<u8>>,
+    pub created: Option<u64>,
+}
+
+impl Metadata {
+    /// True if there is nothing to store
+    pub fn is_empty(&self) -> bool {
+        self.comments.is_empty() && self.icc_profile.is_none() && self.exif.is_none() && self.created.is_none()
+    }
+
+    /// Converts the metadata into chunks, one per comment
+    pub fn to_chunks(&self) -> Vec<Chunk> {
+        let mut chunks: Vec<Chunk> = self
+            .comments
+            .iter()
+            .map(|comment| Chunk { tag: TEXT_TAG, data: comment.as_bytes().to_vec() })
+            .collect();
+
+        if let Some(profile) = &self.icc_profile {
+            chunks.push(Chunk { tag: ICC_TAG, data: profile.clone() });
+        }
+        if let Some(exif) = &self.exif {
+            chunks.push(Chunk { tag: EXIF_TAG, data: exif.clone() });
+        }
+        if let Some(created) = self.created {
+            chunks.push(Chunk { tag: TIME_TAG, data: created.to_be_bytes().to_vec() });
+        }
+
+        chunks
+    }
+
+    /// Collects the metadata from a file's chunks, ignoring chunks it does not know
+    pub fn from_chunks(chunks: &[Chunk]) -> Metadata {
+        let mut metadata = Metadata::default();
+
+        for chunk in chunks {
+            match chunk.tag {
+                TEXT_TAG => metadata.comments.push(String::from_utf8_lossy(&chunk.data).into_owned()),
+                ICC_TAG => metadata.icc_profile = Some(chunk.data.clone()),
+                EXIF_TAG => metadata.exif = Some(chunk.data.clone()),
+                TIME_TAG => metadata.created = chunk.data.as_slice().try_into().ok().map(u64::from_be_bytes),
+                _ => {}
+            }
+        }
+
+        metadata
+    }
+
+    /// Writes the comments, ICC profile, EXIF blob and creation time to files in `dir`
+    pub fn write_sidecars(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+        let mut files = Vec::new();
+        if !self.comments.is_empty() {
+            files.push(("comments.txt", (self.comments.join("\n") + "\n").into_bytes()));
+        }
+        if let Some(profile) = &self.icc_profile {
+            files.push(("profile.icc", profile.clone()));
+        }
+        if let Some(exif) = &self.exif {
+            files.push(("exif.bin", exif.clone()));
+        }
+        if let Some(created) = self.created {
+            files.push(("created.txt", format!("{}\n", format_timestamp(created)).into_bytes()));
+        }
+
+        for (name, contents) in files {
+            let path = dir.join(name);
+            fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats seconds since the Unix epoch as a UTC date and time
+pub fn format_timestamp(secs: u64) -> String {
+    // Convert days since the epoch to a civil date (proleptic Gregorian calendar)
+    let days = (secs / DAY) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let time = secs % DAY;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Describes a chunk in one line for the info command
+pub fn describe_chunk(chunk: &Chunk) -> String {
+    let summary = match chunk.tag {
+        TEXT_TAG => format!("comment \"{}\"", String::from_utf8_lossy(&chunk.data)),
+        ICC_TAG => "ICC profile".to_string(),
+        EXIF_TAG => "EXIF data".to_string(),
+        ALPHA_TAG => "alpha plane".to_string(),
+        SCALE_TAG => "quantizer scale classes".to_string(),
+        CURVE_TAG => match Curve::from_chunk_data(&chunk.data) {
+            Ok(curve) => format!("{} quantization curve", curve.name()),
+            Err(e) => e,
+        },
+        TIME_TAG => match chunk.data.as_slice().try_into() {
+            Ok(secs) => format!("created {}", format_timestamp(u64::from_be_bytes(secs))),
+            Err(_) => "malformed creation time".to_string(),
+        },
+        _ => "unknown chunk".to_string(),
+    };
+
+    format!("{} ({} bytes): {}", chunk.name(), chunk.data.len(), summary)
+}
+
+/// Collects the `#` comments from the header of a PPM or PAM file, without the `#`
+pub fn header_comments(bytes: &[u8]) -> Vec<String> {
+    let mut comments = Vec::new();
+    let mut pos = 0;
+
+    if bytes.starts_with(b"P7") {
+        // PAM headers are line based and end at ENDHDR
+        for line in bytes.split(|&b| b == b'\n') {
+            let line = String::from_utf8_lossy(line);
+            if line.trim() == "ENDHDR" {
+                break;
+            }
+            if let Some(comment) = line.strip_prefix('#') {
+                comments.push(comment.trim().to_string());
+            }
+        }
+        return comments;
+    }
+
+    // PPM headers hold the magic number, width, height and maxval,
+    // with comments allowed anywhere between them
+    let mut tokens = 0;
+    while tokens < 4 && pos < bytes.len() {
+        match bytes[pos] {
+            b'#' => {
+                let end = bytes[pos..].iter().position(|&b| b == b'\n').map_or(bytes.len(), |end| pos + end);
+                comments.push(String::from_utf8_lossy(&bytes[pos + 1..end]).trim().to_string());
+                pos = end;
+            }
+            b if b.is_ascii_whitespace() => pos += 1,
+            _ => {
+                while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() && bytes[pos] != b'#' {
+                    pos += 1;
+                }
+                tokens += 1;
+            }
+        }
+    }
+
+    comments
+}
@@ -0,0 +1,247 @@
+use std::io::Write;
+
+use array2::Array2;
+use csc411_image::Rgb;
+
+/// Largest sample value of the images handed to the codec
+const DENOMINATOR: u32 = 255;
+
+// Struct holding the fields of a PAM header
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PamHeader {
+    width: usize,
+    height: usize,
+    depth: usize,
+    maxval: u32,
+    tupltype: String,
+}
+
+/// Returns true if the bytes look like a PAM file
+pub fn is_pam(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"P7\n")
+}
+
+/// Parses the header lines up to `ENDHDR`, returning the header and the offset of the samples
+fn parse_header(bytes: &[u8]) -> Result<(PamHeader, usize), String> {
+    let mut header = PamHeader { width: 0, height: 0, depth: 0, maxval: 0, tupltype: String::new() };
+    let mut pos = 3;
+
+    loop {
+        let rest = &bytes[pos..];
+        let end = rest.iter().position(|&b| b == b'\n').ok_or("truncated PAM header")?;
+        let line = std::str::from_utf8(&rest[..end]).map_err(|_| "PAM header is not valid text")?;
+        pos += end + 1;
+
+        let mut tokens = line.split_whitespace();
+        let (Some(key), value) = (tokens.next(), tokens.next()) else {
+            continue;
+        };
+        if key.starts_with('#') {
+            continue;
+        }
+
+        let number = || -> Result<usize, String> {
+            value
+                .and_then(|v| v.parse().ok())
+                .ok_or(format!("bad value for {} in PAM header", key))
+        };
+        match key {
+            "ENDHDR" => break,
+            "WIDTH" => header.width = number()?,
+            "HEIGHT" => header.height = number()?,
+            "DEPTH" => header.depth = number()?,
+            "MAXVAL" => header.maxval = number()? as u32,
+            "TUPLTYPE" => header.tupltype = value.unwrap_or("").to_string(),
+            _ => return Err(format!("unknown PAM header field {}", key)),
+        }
+    }
+
+    if header.maxval == 0 || header.maxval > u16::MAX as u32 {
+        return Err("PAM MAXVAL must be between 1 and 65535".to_string());
+    }
+
+    Ok((header, pos))
+}
+
+/// Reads an RGB or RGB_ALPHA PAM, scaling every sample to 0..255.
+/// Returns the colour pixels and, if present, the alpha plane.
+pub fn read_pam(bytes: &[u8]) -> Result<(Array2<Rgb>, Option<Array2<u8>>), String> {
+    if !is_pam(bytes) {
+        return Err("not a PAM file".to_string());
+    }
+
+    let (header, start) = parse_header(bytes)?;
+    let has_alpha = match (header.tupltype.as_str(), header.depth) {
+        ("RGB", 3) => false,
+        ("RGB_ALPHA", 4) => true,
+        (tupltype, depth) => {
+            return Err(format!("unsupported PAM tuple type {} with depth {}", tupltype, depth))
+        }
+    };
+
+    let sample_bytes = if header.maxval > 255 { 2 } else { 1 };
+    let pixels = header.width.checked_mul(header.height).ok_or("PAM image is too large")?;
+    let expected = pixels
+        .checked_mul(header.depth * sample_bytes)
+        .ok_or("PAM image is too large")?;
+    let samples = bytes
+        .get(start..)
+        .filter(|samples| samples.len() >= expected)
+        .ok_or("truncated PAM samples")?;
+
+    // Scale a raw sample to 0..255
+    let scale = |raw: &[u8]| -> u16 {
+        let value = raw.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+        ((value.min(header.maxval) * DENOMINATOR + header.maxval / 2) / header.maxval) as u16
+    };
+
+    let mut rgb = Vec::with_capacity(pixels);
+    let mut alpha = Vec::with_capacity(if has_alpha { pixels } else { 0 });
+
+    for tuple in samples[..expected].chunks(header.depth * sample_bytes) {
+        let mut channels = tuple.chunks(sample_bytes).map(scale);
+        let (red, green, blue) = (channels.next().unwrap(), channels.next().unwrap(), channels.next().unwrap());
+        rgb.push(Rgb { red, green, blue });
+        if has_alpha {
+            alpha.push(channels.next().unwrap() as u8);
+        }
+    }
+
+    let rgb = Array2::from_row_major(header.width, header.height, &rgb);
+    let alpha = has_alpha.then(|| Array2::from_row_major(header.width, header.height, &alpha));
+    Ok((rgb, alpha))
+}
+
+/// Returns true if the bytes look like a PPM file, plain or raw
+pub fn is_ppm(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"P3") || bytes.starts_with(b"P6")
+}
+
+/// Reads the next whitespace separated token from a PPM header, skipping `#` comments
+fn next_token<'a>(bytes: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    while *pos < bytes.len() {
+        match bytes[*pos] {
+            b'#' => {
+                while *pos < bytes.len() && bytes[*pos] != b'\n' {
+                    *pos += 1;
+                }
+            }
+            b if b.is_ascii_whitespace() => *pos += 1,
+            _ => break,
+        }
+    }
+
+    let start = *pos;
+    while *pos < bytes.len() && !bytes[*pos].is_ascii_whitespace() && bytes[*pos] != b'#' {
+        *pos += 1;
+    }
+    (*pos > start).then(|| &bytes[start..*pos])
+}
+
+/// Reads a plain (P3) or raw (P6) PPM, scaling every sample to 0..255
+pub fn read_ppm(bytes: &[u8]) -> Result<Array2<Rgb>, String> {
+    if !is_ppm(bytes) {
+        return Err("not a PPM file".to_string());
+    }
+    let plain = bytes.starts_with(b"P3");
+
+    let mut pos = 2;
+    let mut number = |name: &str| -> Result<u32, String> {
+        next_token(bytes, &mut pos)
+            .and_then(|token| std::str::from_utf8(token).ok())
+            .and_then(|token| token.parse().ok())
+            .ok_or(format!("bad {} in PPM header", name))
+    };
+    let (width, height, maxval) = (number("width")? as usize, number("height")? as usize, number("maxval")?);
+    if maxval == 0 || maxval > u16::MAX as u32 {
+        return Err("PPM maxval must be between 1 and 65535".to_string());
+    }
+
+    let pixels = width.checked_mul(height).ok_or("PPM image is too large")?;
+    let expected = pixels.checked_mul(3).ok_or("PPM image is too large")?;
+
+    // Scale a sample to 0..255
+    let scale = |value: u32| -> u16 { ((value.min(maxval) * DENOMINATOR + maxval / 2) / maxval) as u16 };
+
+    let samples: Vec<u16> = if plain {
+        let mut samples = Vec::with_capacity(expected.min(bytes.len()));
+        while samples.len() < expected {
+            samples.push(scale(number("sample")?));
+        }
+        samples
+    } else {
+        // A single whitespace byte separates the header from the samples
+        let sample_bytes = if maxval > 255 { 2 } else { 1 };
+        let raw = bytes
+            .get(pos + 1..)
+            .filter(|raw| raw.len() >= expected * sample_bytes)
+            .ok_or("truncated PPM samples")?;
+        raw[..expected * sample_bytes]
+            .chunks(sample_bytes)
+            .map(|raw| scale(raw.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32)))
+            .collect()
+    };
+
+    let rgb: Vec<Rgb> = samples
+        .chunks(3)
+        .map(|channels| Rgb { red: channels[0], green: channels[1], blue: channels[2] })
+        .collect();
+    Ok(Array2::from_row_major(width, height, &rgb))
+}
+
+/// Writes each comment as a `#` header line
+fn write_comments(out: &mut (impl Write + ?Sized), comments: &[String]) -> std::io::Result<()> {
+    for comment in comments {
+        // A comment cannot span lines in a header
+        for line in comment.lines() {
+            writeln!(out, "# {}", line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes an 8-bit binary PPM with the given header comments
+pub fn write_raw_ppm(out: &mut (impl Write + ?Sized), rgb: &Array2<Rgb>, comments: &[String]) -> std::io::Result<()> {
+    writeln!(out, "P6")?;
+    write_comments(out, comments)?;
+    writeln!(out, "{} {}", rgb.width(), rgb.height())?;
+    writeln!(out, "{}", DENOMINATOR)?;
+
+    let samples: Vec<u8> = rgb
+        .iter_row_major()
+        .flat_map(|(_, _, pixel)| [pixel.red, pixel.green, pixel.blue].map(|channel| channel.min(255) as u8))
+        .collect();
+
+    out.write_all(&samples)?;
+    out.flush()
+}
+
+/// Writes an 8-bit PAM with the given header comments, and an alpha channel if one is given
+pub fn write_pam(
+    out: &mut (impl Write + ?Sized),
+    rgb: &Array2<Rgb>,
+    alpha: Option<&Array2<u8>>,
+    comments: &[String],
+) -> std::io::Result<()> {
+    let (depth, tupltype) = if alpha.is_some() { (4, "RGB_ALPHA") } else { (3, "RGB") };
+
+    writeln!(out, "P7")?;
+    write_comments(out, comments)?;
+    writeln!(out, "WIDTH {}", rgb.width())?;
+    writeln!(out, "HEIGHT {}", rgb.height())?;
+    writeln!(out, "DEPTH {}", depth)?;
+    writeln!(out, "MAXVAL {}", DENOMINATOR)?;
+    writeln!(out, "TUPLTYPE {}", tupltype)?;
+    writeln!(out, "ENDHDR")?;
+
+    let mut samples = Vec::with_capacity(rgb.width() * rgb.height() * depth);
+    for (x, y, pixel) in rgb.iter_row_major() {
+        samples.extend([pixel.red, pixel.green, pixel.blue].map(|channel| channel.min(255) as u8));
+        if let Some(alpha) = alpha {
+            samples.push(*alpha.get(x, y).unwrap());
+        }
+    }
+
+    out.write_all(&samples)?;
+    out.flush()
+}
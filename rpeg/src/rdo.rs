@@ -0,0 +1,97 @@
+use crate::compress_dct::quantize_dct;
+use crate::block_packing::BlockLayout;
+use crate::curve::Curve;
+
+/// Coefficients closer to zero than this many levels are quantized to zero outright
+const DEAD_ZONE: f32 = 2.0 / 3.0;
+
+/// Converts a coefficient error into the squared error it causes over the
+/// four pixels of a block, in 8-bit units
+const BLOCK_ERROR_SCALE: f32 = 4.0 * 255.0 * 255.0;
+
+// Enum listing how b, c and d are quantized.
+// `RateDistortion` trades squared error against estimated bits, weighted by lambda.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Quantizer {
+    #[default]
+    Round,
+    RateDistortion(f32),
+}
+
+impl Quantizer {
+    /// Parses the lambda given on the command line
+    pub fn parse(lambda: &str) -> Result<Quantizer, String> {
+        match lambda.parse::<f32>() {
+            Ok(lambda) if lambda >= 0.0 && lambda.is_finite() => Ok(Quantizer::RateDistortion(lambda)),
+            _ => Err(format!("bad lambda `{}`, expected a number of at least 0", lambda)),
+        }
+    }
+
+    /// Quantizes the output of `transform_to_dct` to the precision of `layout`,
+    /// with b, c and d quantized on `curve` and their step multiplied by `scale`
+    pub fn quantize(
+        self,
+        dct: (f32, f32, f32, f32),
+        scale: f32,
+        curve: Curve,
+        layout: &BlockLayout,
+    ) -> (u64, i64, i64, i64) {
+        let (a, b, c, d) = dct;
+        match self {
+            Quantizer::Round => quantize_dct(a, b, c, d, scale, curve, layout),
+            Quantizer::RateDistortion(lambda) => quantize_dct_rd(dct, scale, curve, layout, lambda),
+        }
+    }
+}
+
+/// Estimates the bits an entropy coder would spend on a level, using
+/// the length of its signed exponential-Golomb code
+pub fn estimated_bits(level: i64) -> f32 {
+    let zigzag = if level > 0 { 2 * level - 1 } else { -2 * level } as u64;
+    (2 * (u64::BITS - (zigzag + 1).leading_zeros() - 1) + 1) as f32
+}
+
+/// Picks the level for one coefficient, already divided by its quantizer scale,
+/// that minimises the block's squared error plus `lambda` times the estimated bits.
+/// The candidates are the nearest of the levels up to `max_level` on `curve`,
+/// the level below it and zero.
+pub fn quantize_coeff_rd(val: f32, scale: f32, curve: Curve, max_level: i64, lambda: f32) -> i64 {
+    let levels = curve.level(val.abs(), max_level);
+    if levels < DEAD_ZONE {
+        return 0;
+    }
+    let magnitude = (levels.round() as i64).min(max_level);
+    let sign = if val < 0.0 { -1 } else { 1 };
+
+    let cost = |level: i64| {
+        let error = (val - curve.dequantize(level, max_level)) * scale;
+        error * error * BLOCK_ERROR_SCALE + lambda * estimated_bits(level)
+    };
+
+    [magnitude, magnitude - 1, 0]
+        .into_iter()
+        .filter(|&magnitude| magnitude >= 0)
+        .map(|magnitude| sign * magnitude)
+        .min_by(|&x, &y| cost(x).total_cmp(&cost(y)))
+        .unwrap_or(0)
+}
+
+/// Quantizes the output of `transform_to_dct` with the rate-distortion optimised
+/// quantizer. `a` is rounded as usual, b, c and d are quantized on `curve`
+/// to the levels of `layout`, with the step multiplied by `scale`.
+pub fn quantize_dct_rd(
+    dct: (f32, f32, f32, f32),
+    scale: f32,
+    curve: Curve,
+    layout: &BlockLayout,
+    lambda: f32,
+) -> (u64, i64, i64, i64) {
+    let (a, b, c, d) = dct;
+    let max_level = layout.max_level();
+    let a_quan = (a * layout.a_scale()).round() as u64;
+    let b_quan = quantize_coeff_rd(b / scale, scale, curve, max_level, lambda);
+    let c_quan = quantize_coeff_rd(c / scale, scale, curve, max_level, lambda);
+    let d_quan = quantize_coeff_rd(d / scale, scale, curve, max_level, lambda);
+
+    (a_quan, b_quan, c_quan, d_quan)
+}
@@ -0,0 +1,248 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use array2::Array2;
+
+use crate::codec::Vcs;
+use crate::delta::decode_delta_recovering;
+use crate::integrity::Damage;
+use crate::tiling::{is_delta, read_tiled, TiledImage, DELTA_MAGIC};
+
+/// First line of an image sequence file
+pub const SEQUENCE_MAGIC: &str = "Compressed image sequence format 1";
+
+/// Bytes of each frame record header: timestamp, width, height and length
+const RECORD_SIZE: usize = 20;
+
+// Struct describing where one frame sits in a sequence file.
+// `timestamp` is in milliseconds, `offset` is where the frame's bytes start.
+// `delta` frames hold residuals against the frame before them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameEntry {
+    pub timestamp: u64,
+    pub width: usize,
+    pub height: usize,
+    pub delta: bool,
+    pub offset: u64,
+    pub len: usize,
+}
+
+// Struct appending frames to a sequence file
+#[derive(Debug)]
+pub struct SequenceWriter<W: Write> {
+    out: W,
+    frames: usize,
+    last_size: Option<(usize, usize)>,
+}
+
+// Struct holding a decoded frame along with its parsed file and any damage found
+#[derive(Debug, Clone)]
+pub struct DecodedFrame {
+    pub tiled: TiledImage,
+    pub image: Array2<Vcs>,
+    pub damage: Vec<Damage>,
+}
+
+// Struct giving random access to the frames of a sequence file
+#[derive(Debug)]
+pub struct Sequence<R: Read + Seek> {
+    reader: R,
+    pub entries: Vec<FrameEntry>,
+}
+
+/// Returns true if the bytes look like an image sequence file
+pub fn is_sequence(bytes: &[u8]) -> bool {
+    bytes.starts_with(SEQUENCE_MAGIC.as_bytes())
+}
+
+impl<W: Write> SequenceWriter<W> {
+    /// Starts a new sequence by writing the header line
+    pub fn new(mut out: W) -> std::io::Result<SequenceWriter<W>> {
+        writeln!(out, "{}", SEQUENCE_MAGIC)?;
+        Ok(SequenceWriter { out, frames: 0, last_size: None })
+    }
+
+    /// Number of frames appended so far
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Appends one tiled rpeg file as a frame shown at `timestamp` milliseconds.
+    /// The frame's dimensions are copied from its header into the record.
+    /// Residual frames must follow a frame of the same size.
+    pub fn append(&mut self, timestamp: u64, frame: &[u8]) -> Result<(), String> {
+        let tiled = read_tiled(frame)?;
+        if tiled.delta && self.last_size != Some((tiled.width, tiled.height)) {
+            return Err("a residual frame must follow a frame of the same size".to_string());
+        }
+
+        let width = u32::try_from(tiled.width).map_err(|_| "frame is too wide")?;
+        let height = u32::try_from(tiled.height).map_err(|_| "frame is too tall")?;
+        let len = u32::try_from(frame.len()).map_err(|_| "frame is too large")?;
+
+        let mut record = Vec::with_capacity(RECORD_SIZE);
+        record.extend(timestamp.to_be_bytes());
+        record.extend(width.to_be_bytes());
+        record.extend(height.to_be_bytes());
+        record.extend(len.to_be_bytes());
+
+        self.out.write_all(&record).map_err(|e| e.to_string())?;
+        self.out.write_all(frame).map_err(|e| e.to_string())?;
+        self.frames += 1;
+        self.last_size = Some((tiled.width, tiled.height));
+        Ok(())
+    }
+
+    /// Flushes the output and hands it back
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl SequenceWriter<BufWriter<File>> {
+    /// Opens an existing sequence file so more frames can be added to its end
+    pub fn append_to(path: &Path) -> Result<SequenceWriter<BufWriter<File>>, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let entries = Sequence::open(file)?.entries;
+
+        let out = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(SequenceWriter {
+            out: BufWriter::new(out),
+            frames: entries.len(),
+            last_size: entries.last().map(|entry| (entry.width, entry.height)),
+        })
+    }
+}
+
+impl<R: Read + Seek> Sequence<R> {
+    /// Reads the header and walks the frame records to build the index.
+    /// Only the record headers are read, the frames themselves are skipped.
+    pub fn open(mut reader: R) -> Result<Sequence<R>, String> {
+        let file_len = reader.seek(SeekFrom::End(0)).map_err(|e| e.to_string())?;
+        reader.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+
+        let mut magic = vec![0u8; SEQUENCE_MAGIC.len() + 1];
+        reader.read_exact(&mut magic).map_err(|_| "not an image sequence file")?;
+        if !is_sequence(&magic) || magic.last() != Some(&b'\n') {
+            return Err("not an image sequence file".to_string());
+        }
+
+        let mut entries = Vec::new();
+        let mut pos = magic.len() as u64;
+        while pos < file_len {
+            let mut record = [0u8; RECORD_SIZE];
+            reader
+                .read_exact(&mut record)
+                .map_err(|_| format!("frame {} has a truncated record", entries.len()))?;
+
+            let field = |range: std::ops::Range<usize>| -> u64 {
+                record[range].iter().fold(0, |acc, &b| (acc << 8) | b as u64)
+            };
+            let mut entry = FrameEntry {
+                timestamp: field(0..8),
+                width: field(8..12) as usize,
+                height: field(12..16) as usize,
+                delta: false,
+                offset: pos + RECORD_SIZE as u64,
+                len: field(16..20) as usize,
+            };
+
+            // A length running past the end of the file means the frame was cut off
+            pos = entry.offset + entry.len as u64;
+            if pos > file_len {
+                return Err(format!("frame {} is truncated", entries.len()));
+            }
+
+            // The frame's header line says whether it is a keyframe
+            let mut magic = vec![0u8; DELTA_MAGIC.len().min(entry.len)];
+            reader.read_exact(&mut magic).map_err(|e| e.to_string())?;
+            entry.delta = is_delta(&magic);
+
+            reader.seek(SeekFrom::Start(pos)).map_err(|e| e.to_string())?;
+            entries.push(entry);
+        }
+
+        Ok(Sequence { reader, entries })
+    }
+
+    /// Number of frames in the sequence
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// True if the sequence holds no frames
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The index of the last keyframe at or before `index`
+    pub fn keyframe_before(&self, index: usize) -> usize {
+        (0..=index.min(self.len().saturating_sub(1)))
+            .rev()
+            .find(|&i| !self.entries[i].delta)
+            .unwrap_or(0)
+    }
+
+    /// Reads the bytes of the frame at `index`, a complete tiled rpeg file
+    pub fn frame_bytes(&mut self, index: usize) -> Result<Vec<u8>, String> {
+        let entry = *self
+            .entries
+            .get(index)
+            .ok_or(format!("frame {} is out of range, the sequence has {} frames", index, self.len()))?;
+
+        let mut bytes = vec![0u8; entry.len];
+        self.reader.seek(SeekFrom::Start(entry.offset)).map_err(|e| e.to_string())?;
+        self.reader.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+        Ok(bytes)
+    }
+
+    /// Reads the frame at `index` and parses its header and tile index table
+    pub fn frame(&mut self, index: usize) -> Result<TiledImage, String> {
+        let tiled = read_tiled(&self.frame_bytes(index)?)?;
+
+        let entry = &self.entries[index];
+        if (tiled.width, tiled.height) != (entry.width, entry.height) {
+            return Err(format!("frame {} does not match the dimensions in its record", index));
+        }
+        Ok(tiled)
+    }
+
+    /// Decodes the frame at `index`. Residual frames are added onto `previous`,
+    /// the reconstruction of the frame before them. Damaged tiles are returned
+    /// as damage, painted grey in keyframes and left unchanged in residual frames.
+    pub fn decode_next(&mut self, index: usize, previous: Option<&Array2<Vcs>>) -> Result<DecodedFrame, String> {
+        let tiled = self.frame(index)?;
+
+        let (image, damage) = match (tiled.delta, previous) {
+            (false, _) => tiled.decode_recovering(),
+            (true, Some(previous)) => decode_delta_recovering(&tiled, previous)?,
+            (true, None) => return Err(format!("frame {} needs the frame before it to decode", index)),
+        };
+
+        Ok(DecodedFrame { tiled, image, damage })
+    }
+
+    /// Decodes the frame at `index` by decoding forward from the keyframe before it.
+    /// The damage found in every frame along the way is returned with it.
+    pub fn decode_frame(&mut self, index: usize) -> Result<DecodedFrame, String> {
+        if index >= self.len() {
+            return Err(format!("frame {} is out of range, the sequence has {} frames", index, self.len()));
+        }
+
+        let mut decoded = self.decode_next(self.keyframe_before(index), None)?;
+        for next in self.keyframe_before(index) + 1..=index {
+            let frame = self.decode_next(next, Some(&decoded.image))?;
+            decoded = DecodedFrame {
+                damage: decoded.damage.into_iter().chain(frame.damage).collect(),
+                ..frame
+            };
+        }
+
+        Ok(decoded)
+    }
+}
@@ -0,0 +1,480 @@
+use std::io::Write;
+use std::thread;
+
+use array2::Array2;
+
+use crate::adaptive::{tile_classes, unpack_classes, SCALE_TAG};
+use crate::block_packing::BlockLayout;
+use crate::chunks::{find_chunk, read_chunks, write_chunks, Chunk};
+use crate::codec;
+use crate::compress_dct::dct_on_block_scaled;
+use crate::curve::{Curve, CURVE_TAG};
+use crate::integrity::{check_dimensions, crc32, fill_grey, Damage};
+use crate::rdo::Quantizer;
+use crate::uncompress_dct::undo_dct_on_block_scaled;
+use codec::Vcs;
+
+/// Header line identifying a tiled rpeg file
+pub const TILED_MAGIC: &str = "Compressed tiled image format 3";
+
+/// Header line identifying a tiled file of residuals against the previous frame
+pub const DELTA_MAGIC: &str = "Compressed tiled residual format 1";
+
+/// Tile size used when none is given on the command line
+pub const DEFAULT_TILE_SIZE: usize = 256;
+
+/// Number of bytes in one entry of the tile index table
+const ENTRY_SIZE: usize = 12;
+
+// Struct describing where a tile sits in the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+// Struct storing the location and checksum of a tile's words in the payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileEntry {
+    pub offset: u32,
+    pub len: u32,
+    pub checksum: u32,
+}
+
+// Struct representing a parsed tiled rpeg file
+#[derive(Debug, Clone, PartialEq)]
+pub struct TiledImage {
+    pub width: usize,
+    pub height: usize,
+    pub tile_size: usize,
+    pub delta: bool,
+    pub entries: Vec<TileEntry>,
+    pub payload: Vec<u8>,
+    pub chunks: Vec<Chunk>,
+    pub chunk_error: Option<String>,
+    pub classes: Option<Array2<u8>>,
+    pub curve: Curve,
+    pub layout: BlockLayout,
+}
+
+/// Splits an image of the given (even) dimensions into tiles in row-major order.
+/// Tiles on the right and bottom edges may be smaller than `tile_size`.
+pub fn tile_rects(width: usize, height: usize, tile_size: usize) -> Vec<TileRect> {
+    let mut rects = Vec::new();
+
+    for y in (0..height).step_by(tile_size) {
+        for x in (0..width).step_by(tile_size) {
+            rects.push(TileRect {
+                x,
+                y,
+                width: tile_size.min(width - x),
+                height: tile_size.min(height - y),
+            });
+        }
+    }
+
+    rects
+}
+
+/// Number of words in a residual tile's bitmap of changed blocks
+pub fn bitmap_words(blocks: usize) -> usize {
+    blocks.div_ceil(32)
+}
+
+/// Number of tiles needed to cover `length` pixels
+fn tiles_across(length: usize, tile_size: usize) -> usize {
+    length.div_ceil(tile_size)
+}
+
+/// Copies the pixels covered by `rect` out of the image
+pub fn extract_tile(img: &Array2<Vcs>, rect: &TileRect) -> Array2<Vcs> {
+    let mut tile_data = Vec::with_capacity(rect.width * rect.height);
+
+    for y in rect.y..rect.y + rect.height {
+        for x in rect.x..rect.x + rect.width {
+            tile_data.push(img.get(x, y).unwrap().clone());
+        }
+    }
+
+    Array2::from_row_major(rect.width, rect.height, &tile_data)
+}
+
+/// Copies a decoded tile back into its place in the image
+pub fn paste_tile(img: &mut Array2<Vcs>, rect: &TileRect, tile: &Array2<Vcs>) {
+    for (x, y, pixel) in tile.iter_row_major() {
+        img.set(rect.x + x, rect.y + y, pixel.clone());
+    }
+}
+
+/// Applies `f` to every item, spreading the items across the available threads.
+/// Results are returned in the same order as the items.
+pub(crate) fn parallel_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let per_thread = items.len().div_ceil(threads).max(1);
+    let f = &f;
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(per_thread)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// Compresses every tile of the image independently, spreading the tiles across threads
+pub fn encode_tiles(img: &Array2<Vcs>, tile_size: usize) -> Vec<Vec<u64>> {
+    encode_tiles_scaled(img, tile_size, None, Quantizer::Round, Curve::Linear, &BlockLayout::LEGACY)
+}
+
+/// Compresses every tile of an image in parallel, quantizing each block with
+/// the scale of its class in `classes` (one class per block of the whole image),
+/// the given quantizer and the given curve, and packing it as `layout` says
+pub fn encode_tiles_scaled(
+    img: &Array2<Vcs>,
+    tile_size: usize,
+    classes: Option<&Array2<u8>>,
+    quantizer: Quantizer,
+    curve: Curve,
+    layout: &BlockLayout,
+) -> Vec<Vec<u64>> {
+    let rects = tile_rects(img.width(), img.height(), tile_size);
+    parallel_map(&rects, |rect| {
+        let classes = classes.map(|classes| tile_classes(classes, rect));
+        let tile = extract_tile(img, rect);
+        dct_on_block_scaled(tile, rect.height, rect.width, classes.as_ref(), quantizer, curve, layout)
+    })
+}
+
+/// Writes a tiled rpeg file: the header, the tile index table, every tile's words
+/// and then any extra chunks.
+/// Each table entry holds the tile's offset, length and CRC-32 checksum.
+/// Words laid out other than the legacy way have their size added to the header.
+pub fn write_tiled(
+    out: &mut impl Write,
+    width: usize,
+    height: usize,
+    tile_size: usize,
+    layout: &BlockLayout,
+    tiles: &[Vec<u64>],
+    chunks: &[Chunk],
+) -> std::io::Result<()> {
+    let fields = header_fields(width, height, tile_size, layout, tiles);
+    write_with_magic(out, TILED_MAGIC, &fields, layout, tiles, chunks)
+}
+
+/// Writes a tiled file of residual tiles, laid out like `write_tiled`
+pub fn write_delta(
+    out: &mut impl Write,
+    width: usize,
+    height: usize,
+    tile_size: usize,
+    layout: &BlockLayout,
+    tiles: &[Vec<u64>],
+    chunks: &[Chunk],
+) -> std::io::Result<()> {
+    let fields = header_fields(width, height, tile_size, layout, tiles);
+    write_with_magic(out, DELTA_MAGIC, &fields, layout, tiles, chunks)
+}
+
+/// The numbers on the second header line. The word size is only
+/// written for layouts other than the legacy one.
+fn header_fields(
+    width: usize,
+    height: usize,
+    tile_size: usize,
+    layout: &BlockLayout,
+    tiles: &[Vec<u64>],
+) -> Vec<usize> {
+    let mut fields = vec![width, height, tile_size, tiles.len()];
+    if *layout != BlockLayout::LEGACY {
+        fields.push(layout.word_bits);
+    }
+    fields
+}
+
+/// Writes the header line `magic` followed by the rest of a tiled file
+fn write_with_magic(
+    out: &mut impl Write,
+    magic: &str,
+    fields: &[usize],
+    layout: &BlockLayout,
+    tiles: &[Vec<u64>],
+    chunks: &[Chunk],
+) -> std::io::Result<()> {
+    let tiles: Vec<Vec<u8>> = tiles.iter().map(|tile| layout.words_to_bytes(tile)).collect();
+
+    // The index stores 32-bit offsets, so every tile must end within 4 GiB of the first
+    let too_large = || std::io::Error::new(std::io::ErrorKind::InvalidInput, "tiles take more than 4 GiB");
+    let mut index = Vec::with_capacity(tiles.len());
+    let mut offset = 0u32;
+    for tile in &tiles {
+        let len = u32::try_from(tile.len()).map_err(|_| too_large())?;
+        index.push((offset, len, crc32(tile)));
+        offset = offset.checked_add(len).ok_or_else(too_large)?;
+    }
+
+    writeln!(out, "{}", magic)?;
+    let fields: Vec<String> = fields.iter().map(|field| field.to_string()).collect();
+    writeln!(out, "{}", fields.join(" "))?;
+
+    for (offset, len, crc) in index {
+        out.write_all(&offset.to_be_bytes())?;
+        out.write_all(&len.to_be_bytes())?;
+        out.write_all(&crc.to_be_bytes())?;
+    }
+
+    for tile in &tiles {
+        out.write_all(tile)?;
+    }
+
+    write_chunks(out, chunks)?;
+
+    out.flush()
+}
+
+/// Reads one newline terminated header line starting at `pos`, advancing `pos` past it
+pub(crate) fn read_line<'a>(bytes: &'a [u8], pos: &mut usize) -> Result<&'a str, String> {
+    let rest = &bytes[*pos..];
+    let end = rest
+        .iter()
+        .position(|&b| b == b'\n')
+        .ok_or("truncated header")?;
+    *pos += end + 1;
+    std::str::from_utf8(&rest[..end]).map_err(|_| "header is not valid text".to_string())
+}
+
+/// Parses a whitespace separated list of numbers from a header line
+pub(crate) fn parse_numbers(line: &str) -> Result<Vec<usize>, String> {
+    line.split_whitespace()
+        .map(|n| n.parse::<usize>().map_err(|_| format!("bad number `{}` in header", n)))
+        .collect()
+}
+
+/// Returns true if the bytes look like a tiled rpeg file, of either kind
+pub fn is_tiled(bytes: &[u8]) -> bool {
+    bytes.starts_with(TILED_MAGIC.as_bytes()) || is_delta(bytes)
+}
+
+/// Returns true if the bytes look like a tiled file of residuals
+pub fn is_delta(bytes: &[u8]) -> bool {
+    bytes.starts_with(DELTA_MAGIC.as_bytes())
+}
+
+/// Parses the header and tile index table of a tiled rpeg file
+pub fn read_tiled(bytes: &[u8]) -> Result<TiledImage, String> {
+    let mut pos = 0;
+
+    let delta = match read_line(bytes, &mut pos)? {
+        TILED_MAGIC => false,
+        DELTA_MAGIC => true,
+        _ => return Err("not a tiled rpeg file".to_string()),
+    };
+
+    // Files in the legacy layout leave out the word size
+    let fields = parse_numbers(read_line(bytes, &mut pos)?)?;
+    let (width, height, tile_size, tile_count, layout) = match fields[..] {
+        [width, height, tile_size, tile_count] => (width, height, tile_size, tile_count, BlockLayout::LEGACY),
+        [width, height, tile_size, tile_count, word_bits] => {
+            (width, height, tile_size, tile_count, BlockLayout::from_word_bits(word_bits)?)
+        }
+        _ => return Err("expected width, height, tile size, tile count and an optional word size".to_string()),
+    };
+
+    if tile_size == 0 || !tile_size.is_multiple_of(2) || !width.is_multiple_of(2) || !height.is_multiple_of(2) {
+        return Err("tile size and dimensions must be even".to_string());
+    }
+    check_dimensions(width, height)?;
+    if Some(tile_count) != tiles_across(width, tile_size).checked_mul(tiles_across(height, tile_size)) {
+        return Err("tile count does not match the image dimensions".to_string());
+    }
+
+    let table_end = tile_count
+        .checked_mul(ENTRY_SIZE)
+        .and_then(|len| len.checked_add(pos))
+        .filter(|&end| end <= bytes.len())
+        .ok_or("truncated tile index table")?;
+
+    let entries: Vec<TileEntry> = bytes[pos..table_end]
+        .chunks(ENTRY_SIZE)
+        .map(|entry| TileEntry {
+            offset: u32::from_be_bytes(entry[0..4].try_into().unwrap()),
+            len: u32::from_be_bytes(entry[4..8].try_into().unwrap()),
+            checksum: u32::from_be_bytes(entry[8..12].try_into().unwrap()),
+        })
+        .collect();
+
+    // Tiles are written back to back, so the chunks start where the last tile ends
+    let payload_len: usize = entries.iter().map(|entry| entry.len as usize).sum();
+    let payload_end = table_end.saturating_add(payload_len).min(bytes.len());
+    let (chunks, mut chunk_error) = read_chunks(&bytes[payload_end..]);
+
+    // Blocks are quantized with the default scale unless classes are signalled.
+    // A damaged chunk falls back to the default and is reported like any other.
+    let classes = match find_chunk(&chunks, &SCALE_TAG).map(|chunk| unpack_classes(&chunk.data, width / 2, height / 2)) {
+        Some(Ok(classes)) => Some(classes),
+        Some(Err(reason)) => {
+            chunk_error.get_or_insert(reason);
+            None
+        }
+        None => None,
+    };
+
+    // Coefficients are on the linear curve unless another one is signalled
+    let curve = match find_chunk(&chunks, &CURVE_TAG).map(|chunk| Curve::from_chunk_data(&chunk.data)) {
+        Some(Ok(curve)) => curve,
+        Some(Err(reason)) => {
+            chunk_error.get_or_insert(reason);
+            Curve::Linear
+        }
+        None => Curve::Linear,
+    };
+
+    Ok(TiledImage {
+        width,
+        height,
+        tile_size,
+        delta,
+        entries,
+        payload: bytes[table_end..payload_end].to_vec(),
+        chunks,
+        chunk_error,
+        classes,
+        curve,
+        layout,
+    })
+}
+
+impl TiledImage {
+    /// The position of every tile in the image
+    pub fn rects(&self) -> Vec<TileRect> {
+        tile_rects(self.width, self.height, self.tile_size)
+    }
+
+    /// The position of the tile at `index` in row-major tile order
+    pub fn rect(&self, index: usize) -> TileRect {
+        let across = tiles_across(self.width, self.tile_size);
+        let x = index % across * self.tile_size;
+        let y = index / across * self.tile_size;
+        TileRect {
+            x,
+            y,
+            width: self.tile_size.min(self.width - x),
+            height: self.tile_size.min(self.height - y),
+        }
+    }
+
+    /// Returns the packed words of a single tile without touching any other tile.
+    /// Fails if the tile is truncated or its checksum does not match.
+    pub fn tile_words(&self, index: usize) -> Result<Vec<u64>, String> {
+        let entry = self.entries.get(index).ok_or("tile index out of range")?;
+        let rect = self.rect(index);
+        let start = entry.offset as usize;
+        let end = start.saturating_add(entry.len as usize);
+
+        // Residual tiles only hold the blocks that changed, after a bitmap of which ones did
+        let blocks = rect.width / 2 * rect.height / 2;
+        let word_bytes = self.layout.word_bytes();
+        let valid_len = if self.delta {
+            let words = entry.len as usize / word_bytes;
+            (entry.len as usize).is_multiple_of(word_bytes)
+                && words >= bitmap_words(blocks)
+                && words <= bitmap_words(blocks) + blocks
+        } else {
+            entry.len as usize == blocks * word_bytes
+        };
+        if !valid_len {
+            return Err(format!("tile {} has the wrong length", index));
+        }
+        if end > self.payload.len() {
+            return Err(format!("tile {} runs past the end of the file", index));
+        }
+        if crc32(&self.payload[start..end]) != entry.checksum {
+            return Err(format!("tile {} failed its checksum", index));
+        }
+
+        Ok(self.layout.words_from_bytes(&self.payload[start..end]))
+    }
+
+    /// Decodes a single tile into Vcs values
+    pub fn decode_tile(&self, index: usize) -> Result<Array2<Vcs>, String> {
+        if self.delta {
+            return Err("residual tiles need the previous frame to decode".to_string());
+        }
+        let words = self.tile_words(index)?;
+        let rect = self.rect(index);
+        let classes = self.classes.as_ref().map(|classes| tile_classes(classes, &rect));
+        Ok(undo_dct_on_block_scaled(words, rect.height, rect.width, classes.as_ref(), self.curve, &self.layout))
+    }
+
+    /// Checks every tile's and chunk's length and checksum without decoding anything.
+    /// A damaged chunk is reported as covering the whole image.
+    pub fn verify(&self) -> Vec<Damage> {
+        let mut damage: Vec<Damage> = (0..self.entries.len())
+            .filter_map(|index| {
+                self.tile_words(index)
+                    .err()
+                    .map(|reason| self.damage(index, reason))
+            })
+            .collect();
+
+        if let Some(reason) = &self.chunk_error {
+            damage.push(Damage { x: 0, y: 0, width: self.width, height: self.height, reason: reason.clone() });
+        }
+
+        damage
+    }
+
+    /// Describes the region covered by a tile that failed to decode
+    pub fn damage(&self, index: usize, reason: String) -> Damage {
+        let rect = self.rect(index);
+        Damage {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+            reason,
+        }
+    }
+
+    /// Decodes every tile in parallel and assembles the full image.
+    /// Tiles that cannot be decoded are painted grey and returned as damaged regions.
+    pub fn decode_recovering(&self) -> (Array2<Vcs>, Vec<Damage>) {
+        let rects = self.rects();
+        let blank = Vcs { y: 0.0, pb: 0.0, pr: 0.0 };
+        let mut img = Array2::new(self.width, self.height, blank);
+        let mut damage = Vec::new();
+
+        let indices: Vec<usize> = (0..rects.len()).collect();
+        let decoded = parallel_map(&indices, |&index| self.decode_tile(index));
+
+        for (index, (rect, tile)) in rects.iter().zip(decoded).enumerate() {
+            match tile {
+                Ok(tile) => paste_tile(&mut img, rect, &tile),
+                Err(e) => {
+                    let region = self.damage(index, e);
+                    fill_grey(&mut img, &region);
+                    damage.push(region);
+                }
+            }
+        }
+
+        (img, damage)
+    }
+
+    /// Decodes every tile in parallel and assembles the full image.
+    /// Fails on the first tile that cannot be decoded.
+    pub fn decode(&self) -> Result<Array2<Vcs>, String> {
+        let (img, damage) = self.decode_recovering();
+        match damage.first() {
+            Some(region) => Err(region.reason.clone()),
+            None => Ok(img),
+        }
+    }
+}
@@ -1,25 +1,28 @@
-use csc411_arith::chroma_of_index;
-use crate::block_packing::unpack_block;
+use crate::block_packing::{unpack_blocks, BlockLayout};
+use crate::adaptive::{scale_of_class, DEFAULT_CLASS};
+use crate::curve::Curve;
 use array2::Array2;
+use bitpack::word::Word;
 
 use crate::codec;
 use codec::Vcs;
 
-/// Reverts the quantization of a DCT coefficient
-fn undo_quantize(coeff: i64) -> f32 {
-    if coeff.abs() == 15 {
-        coeff as f32 * 0.3 / 15.0
-    } else {
-        coeff as f32 / 50.0
-    }
-}
-
-/// Reverts the quantization of DCT coefficients
-pub fn undo_quantiza_dct(a_quan: u64, b_quan: i64, c_quan: i64, d_quan: i64) -> (f32, f32, f32, f32) {
-    let a = a_quan as f32 / 511.0;
-    let b = undo_quantize(b_quan);
-    let c = undo_quantize(c_quan);
-    let d = undo_quantize(d_quan);
+/// Reverts the quantization of DCT coefficients quantized to the precision
+/// of `layout` with `scale` on `curve`
+pub fn undo_quantiza_dct(
+    a_quan: u64,
+    b_quan: i64,
+    c_quan: i64,
+    d_quan: i64,
+    scale: f32,
+    curve: Curve,
+    layout: &BlockLayout,
+) -> (f32, f32, f32, f32) {
+    let max_level = layout.max_level();
+    let a = a_quan as f32 / layout.a_scale();
+    let b = curve.dequantize(b_quan, max_level) * scale;
+    let c = curve.dequantize(c_quan, max_level) * scale;
+    let d = curve.dequantize(d_quan, max_level) * scale;
     (a, b, c, d)
 }
 
@@ -37,40 +40,54 @@ pub fn undo_dct(a: f32, b: f32, c: f32, d: f32) -> Array2<Vcs> {
     ], )
 }
 
-/// Reverts DCT and quantization operations on blocks of an image
+/// Reverts DCT and quantization operations on blocks of an image packed in 32-bit legacy words
 pub fn undo_dct_on_block(compressed_vec: Vec<u32>, height: usize, width: usize) -> Array2<Vcs>{
+    undo_dct_on_block_scaled(compressed_vec, height, width, None, Curve::Linear, &BlockLayout::LEGACY)
+}
+
+/// Reverts DCT and quantization operations on blocks of an image packed as
+/// `layout` says, using the scale of each block's class (one class per block,
+/// row-major) and the curve the coefficients were quantized on
+pub fn undo_dct_on_block_scaled<W: Word>(
+    compressed_vec: Vec<W>,
+    height: usize,
+    width: usize,
+    classes: Option<&Array2<u8>>,
+    curve: Curve,
+    layout: &BlockLayout,
+) -> Array2<Vcs>{
     // Initialize an array to store Vcs data
     let temp = Vcs { y: 0.0, pb: 0.0, pr: 0.0 };
-    let vcs = vec![temp; width as usize * height as usize];
-    let mut vcs_image_array = Array2::from_row_major(width, height as usize, &vcs);
+    let vcs = vec![temp; width * height];
+    let mut vcs_image_array = Array2::from_row_major(width, height, &vcs);
+
+    // Unpack every field of every block up front
+    let [a_values, b_values, c_values, d_values, pb_values, pr_values] = unpack_blocks(layout, &compressed_vec);
 
     // Iterate over blocks in the compressed image
-    for y in (0..height as usize).step_by(2) {
-        for x in (0..width as usize).step_by(2) {
+    for y in (0..height).step_by(2) {
+        for x in (0..width).step_by(2) {
             // Calculate index in the compressed vector
-            let index = (y / 2 * width as usize / 2) + x / 2;
-
-            // Unpack the compressed block
-            let packed_word = compressed_vec[index];
-            let (quantized_a, quantized_b, quantized_c, quantized_d, chroma_pb_index, chroma_pr_index) = unpack_block(packed_word);
+            let index = (y / 2 * width / 2) + x / 2;
 
-            // Unwrap and convert quantized coefficients and chroma indices
-            let quantized_a = quantized_a.unwrap_or(0) as u64;
-            let quantized_b = quantized_b.unwrap_or(0) as i64;
-            let quantized_c = quantized_c.unwrap_or(0) as i64;
-            let quantized_d = quantized_d.unwrap_or(0) as i64;
-            let chroma_pb_index = chroma_pb_index.unwrap_or(0) as u64;
-            let chroma_pr_index = chroma_pr_index.unwrap_or(0) as u64;
+            // Convert quantized coefficients and chroma indices
+            let quantized_a = a_values[index] as u64;
+            let quantized_b = b_values[index];
+            let quantized_c = c_values[index];
+            let quantized_d = d_values[index];
+            let chroma_pb_index = pb_values[index] as u64;
+            let chroma_pr_index = pr_values[index] as u64;
 
             // Undo quantization
-            let (dequantized_a, dequantized_b, dequantized_c, dequantized_d) = undo_quantiza_dct(quantized_a, quantized_b, quantized_c, quantized_d);
+            let class = classes.map_or(DEFAULT_CLASS, |classes| *classes.get(x / 2, y / 2).unwrap());
+            let (dequantized_a, dequantized_b, dequantized_c, dequantized_d) = undo_quantiza_dct(quantized_a, quantized_b, quantized_c, quantized_d, scale_of_class(class), curve, layout);
             
             // Revert DCT transformation
             let decompressed_block = undo_dct(dequantized_a, dequantized_b, dequantized_c, dequantized_d);
 
             // Obtain chroma values from chroma indices
-            let pb = chroma_of_index(chroma_pb_index.try_into().unwrap());
-            let pr = chroma_of_index(chroma_pr_index.try_into().unwrap());
+            let pb = layout.pb.chroma_value(chroma_pb_index);
+            let pr = layout.pr.chroma_value(chroma_pr_index);
 
             // Set reconstructed Vcs block in the Vcs image array
             for (delta_y, delta_x) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
@@ -15,9 +15,9 @@ pub fn vcs_to_rgb(vcs_image: &Array2<Vcs>) -> Array2<Rgb> {
             let pixel = vcs_image.get(x, y).unwrap();
             
             // Convert YpbPr to Rgb
-            let red = (pixel.y + 1.402 * pixel.pr).min(1.0).max(0.0) * 255.0;
-            let green = (pixel.y - 0.344136 * pixel.pb - 0.714136 * pixel.pr).min(1.0).max(0.0) * 255.0;
-            let blue = (pixel.y + 1.772 * pixel.pb).min(1.0).max(0.0) * 255.0;
+            let red = (pixel.y + 1.402 * pixel.pr).clamp(0.0, 1.0) * 255.0;
+            let green = (pixel.y - 0.344136 * pixel.pb - 0.714136 * pixel.pr).clamp(0.0, 1.0) * 255.0;
+            let blue = (pixel.y + 1.772 * pixel.pb).clamp(0.0, 1.0) * 255.0;
             
             // Create Rgb pixel and push it to the vector
             rgb_vec.push(Rgb { red: red as u16, green: green as u16, blue: blue as u16 });
@@ -0,0 +1,142 @@
+use rpeg::block_packing::{
+    pack_block, unpack_block, unpack_blocks, BlockFields, BlockLayout, Field, HighBlock, LegacyBlock, NearLosslessBlock,
+};
+
+#[test]
+fn legacy_word_has_the_documented_layout() {
+    // a:9@23, b:5@18, c:5@13, d:5@8, pb:4@4, pr:4@0
+    let word: u32 = pack_block(&BlockLayout::LEGACY, 511, -15, 15, 0, 0xA, 0x5).unwrap();
+    assert_eq!(word, 0xFFC5_E0A5);
+    let fields = BlockFields { a: 511, b: -15, c: 15, d: 0, pb: 0xA, pr: 0x5 };
+    assert_eq!(unpack_block(&BlockLayout::LEGACY, word), Some(fields));
+}
+
+#[test]
+fn block_structs_pack_like_their_layouts() {
+    let legacy = LegacyBlock { a: 511, b: -15, c: 15, d: 0, pb: 0xA, pr: 0x5 };
+    assert_eq!(legacy.pack(), Ok(0xFFC5_E0A5));
+    assert_eq!(LegacyBlock::unpack(0xFFC5_E0A5), legacy);
+
+    let high = HighBlock { a: 4095, b: -128, c: 127, d: 1, pb: 63, pr: 0 };
+    let word: u64 = pack_block(&BlockLayout::HIGH, 4095, -128, 127, 1, 63, 0).unwrap();
+    assert_eq!(high.pack(), Ok(word));
+
+    let near = NearLosslessBlock { a: 1, b: -1024, c: 1023, d: -1, pb: 0, pr: 511 };
+    let word: u64 = pack_block(&BlockLayout::NEAR_LOSSLESS, 1, -1024, 1023, -1, 0, 511).unwrap();
+    assert_eq!(NearLosslessBlock::unpack(word), near);
+
+    assert!(LegacyBlock { a: 512, ..legacy }.pack().is_err());
+}
+
+#[test]
+fn every_layout_round_trips_its_extreme_values() {
+    for layout in BlockLayout::PROFILES {
+        let top = layout.max_level();
+        let a = layout.a.max() as u64;
+        let (pb, pr) = (layout.pb.max() as u64, layout.pr.max() as u64);
+        for (b, c, d) in [(top, -top, 0), (-top, top, -1), (0, 0, 0), (1, -1, top)] {
+            let word: u64 = pack_block(&layout, a, b, c, d, pb, pr).unwrap();
+            assert_eq!(word.checked_shr(layout.word_bits as u32).unwrap_or(0), 0, "{}", layout.name);
+            assert_eq!(unpack_block(&layout, word), Some(BlockFields { a, b, c, d, pb, pr }), "{}", layout.name);
+        }
+    }
+}
+
+#[test]
+fn fields_tile_the_word_without_overlapping() {
+    for layout in BlockLayout::PROFILES {
+        let fields = [layout.pr, layout.pb, layout.d, layout.c, layout.b, layout.a];
+        let mut next = 0;
+        for field in fields {
+            assert_eq!(field.lsb, next, "{}", layout.name);
+            next += field.width;
+        }
+        assert_eq!(next as usize, layout.word_bits, "{}", layout.name);
+    }
+}
+
+#[test]
+fn layouts_pack_into_any_word_they_fit() {
+    for layout in BlockLayout::PROFILES {
+        let wide: u128 = pack_block(&layout, 1, -2, 3, -4, 5, 6).unwrap();
+        let word: u64 = pack_block(&layout, 1, -2, 3, -4, 5, 6).unwrap();
+        assert_eq!(wide, u128::from(word), "{}", layout.name);
+        assert_eq!(unpack_block(&layout, wide), unpack_block(&layout, word), "{}", layout.name);
+    }
+
+    // The 48-bit layout's `a` field lies past the end of a 32-bit word
+    assert_eq!(BlockLayout::HIGH.a.put(0u32, 1), None);
+    assert_eq!(BlockLayout::HIGH.a.get(u32::MAX), None);
+    assert_eq!(BlockLayout::LEGACY.a.get(u32::MAX), Some(511));
+    assert_eq!(pack_block::<u32>(&BlockLayout::HIGH, 1, -2, 3, -4, 5, 6), None);
+    assert_eq!(unpack_block(&BlockLayout::HIGH, u32::MAX), None);
+}
+
+#[test]
+fn bulk_unpacking_matches_unpack_block() {
+    for layout in BlockLayout::PROFILES {
+        let words: Vec<u64> = (0..64u64).map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - layout.word_bits)).collect();
+        let columns = unpack_blocks(&layout, &words);
+        for (index, &word) in words.iter().enumerate() {
+            let BlockFields { a, b, c, d, pb, pr } = unpack_block(&layout, word).unwrap();
+            let fields = [a as i64, b, c, d, pb as i64, pr as i64];
+            for (column, field) in columns.iter().zip(fields) {
+                assert_eq!(column[index], field, "{}", layout.name);
+            }
+        }
+    }
+}
+
+#[test]
+fn words_survive_conversion_to_bytes() {
+    for layout in BlockLayout::PROFILES {
+        let words: Vec<u64> = [pack_block(&layout, 1, -2, 3, -4, 5, 6), pack_block(&layout, 0, 0, 0, 0, 0, 0)]
+            .into_iter()
+            .map(Option::unwrap)
+            .collect();
+        let bytes = layout.words_to_bytes(&words);
+        assert_eq!(bytes.len(), 2 * layout.word_bytes());
+        assert_eq!(&bytes[..layout.word_bytes()], &words[0].to_be_bytes()[8 - layout.word_bytes()..]);
+        assert_eq!(layout.words_from_bytes(&bytes), words);
+    }
+}
+
+#[test]
+fn profiles_are_found_by_name_and_word_size() {
+    for layout in BlockLayout::PROFILES {
+        assert_eq!(BlockLayout::parse(layout.name), Ok(layout));
+        assert_eq!(BlockLayout::from_word_bits(layout.word_bits), Ok(layout));
+    }
+    assert!(BlockLayout::parse("huge").is_err());
+    assert!(BlockLayout::from_word_bits(40).is_err());
+}
+
+#[test]
+fn fields_reject_values_that_do_not_fit() {
+    let field = Field::signed(5, 8);
+    assert_eq!(field.put(0u64, 15), Some(15 << 8));
+    assert_eq!(field.put(0u64, 16), None);
+    assert_eq!(field.put(0u64, -17), None);
+    assert_eq!(Field::unsigned(4, 0).put(0u64, -1), None);
+
+    // A block with any value out of range does not pack
+    let layout = BlockLayout::LEGACY;
+    assert_eq!(pack_block::<u32>(&layout, 512, 0, 0, 0, 0, 0), None);
+    assert_eq!(pack_block::<u32>(&layout, 0, 0, 16, 0, 0, 0), None);
+    assert_eq!(pack_block::<u32>(&layout, 0, 0, 0, 0, 0, 16), None);
+    assert_eq!(pack_block::<u32>(&layout, u64::MAX, 0, 0, 0, 0, 0), None);
+}
+
+#[test]
+fn uniform_chroma_puts_zero_in_the_middle() {
+    for layout in [BlockLayout::HIGH, BlockLayout::NEAR_LOSSLESS] {
+        let field = layout.pb;
+        let zero = field.chroma_index(0.0);
+        assert_eq!(zero as i64, field.max() / 2);
+        assert_eq!(field.chroma_value(zero), 0.0);
+        for value in [-0.5, -0.2, 0.1, 0.37, 0.5] {
+            let step = 0.5 / (field.max() / 2) as f32;
+            assert!((field.chroma_value(field.chroma_index(value)) - value).abs() <= step / 2.0 + 1e-6);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+// Helpers shared by the integration tests
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::io::Write as _;
+use std::process::{Command, Output, Stdio};
+
+use array2::Array2;
+use csc411_image::{Rgb, RgbImage, Write};
+use rpeg::codec::Vcs;
+use rpeg::compress_pixels::{convert_to_floats, convert_to_vcs};
+use rpeg::formats::{load_image, save_image, Image};
+
+/// Directory holding the golden files
+pub const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+/// An image of a single colour
+pub fn flat(width: usize, height: usize, red: u16, green: u16, blue: u16) -> Array2<Rgb> {
+    Array2::new(width, height, Rgb { red, green, blue })
+}
+
+/// An image whose red rises by 3 a pixel left to right and whose green
+/// rises by 3 a pixel top to bottom
+pub fn gradient(width: usize, height: usize) -> Array2<Rgb> {
+    let pixels: Vec<Rgb> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| Rgb {
+            red: (80 + 3 * x).min(255) as u16,
+            green: (80 + 3 * y).min(255) as u16,
+            blue: 128,
+        })
+        .collect();
+    Array2::from_row_major(width, height, &pixels)
+}
+
+/// An image of dark and light squares `cell` pixels wide
+pub fn checkerboard(width: usize, height: usize, cell: usize) -> Array2<Rgb> {
+    let pixels: Vec<Rgb> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let level = if (x / cell + y / cell).is_multiple_of(2) { 64 } else { 192 };
+            Rgb { red: level, green: level, blue: level }
+        })
+        .collect();
+    Array2::from_row_major(width, height, &pixels)
+}
+
+/// Converts an image to the colour space the codec works in
+pub fn to_vcs(img: &Array2<Rgb>) -> Array2<Vcs> {
+    convert_to_vcs(convert_to_floats(img.clone()))
+}
+
+/// The peak signal-to-noise ratio of `decoded` against `original`, in dB,
+/// over the pixels they share
+pub fn psnr(original: &Array2<Rgb>, decoded: &Array2<Rgb>) -> f64 {
+    let (width, height) = (original.width().min(decoded.width()), original.height().min(decoded.height()));
+    let mut squared = 0.0;
+    for y in 0..height {
+        for x in 0..width {
+            let (a, b) = (original.get(x, y).unwrap(), decoded.get(x, y).unwrap());
+            for (a, b) in [(a.red, b.red), (a.green, b.green), (a.blue, b.blue)] {
+                squared += (a as f64 - b as f64).powi(2);
+            }
+        }
+    }
+    let mse = squared / (width * height * 3) as f64;
+    if mse == 0.0 { f64::INFINITY } else { 10.0 * (255.0 * 255.0 / mse).log10() }
+}
+
+// Struct owning a scratch directory that is removed when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates an empty scratch directory unique to this test
+    pub fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("rpeg-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    /// The path of `name` inside the directory
+    pub fn join(&self, name: &str) -> String {
+        self.0.join(name).to_str().unwrap().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Writes an image as a PPM file
+pub fn write_ppm(path: &str, img: &Array2<Rgb>) {
+    let pixels = img.iter_row_major().map(|(_, _, pixel)| pixel.clone()).collect();
+    let image = RgbImage { pixels, width: img.width() as u32, height: img.height() as u32, denominator: 255 };
+    image.write(Some(path)).unwrap();
+}
+
+/// Writes an image as a PAM file
+pub fn write_pam(path: &str, img: &Array2<Rgb>) {
+    let image = Image { pixels: img.clone(), alpha: None, comments: Vec::new() };
+    save_image(&image, Some(path)).unwrap();
+}
+
+/// Reads an image written by the binary
+pub fn read_pam(path: &str) -> Array2<Rgb> {
+    load_image(Some(path)).unwrap().pixels
+}
+
+/// Runs the rpeg binary
+pub fn rpeg(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rpeg")).args(args).output().unwrap()
+}
+
+/// Runs the rpeg binary with `input` on its standard input
+pub fn rpeg_with_input(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rpeg"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Compresses `img` with the given flags and decompresses it again,
+/// returning the decoded image
+pub fn round_trip(dir: &TempDir, img: &Array2<Rgb>, flags: &[&str]) -> Array2<Rgb> {
+    let (input, compressed, output) = (dir.join("in.pam"), dir.join("out.rpeg"), dir.join("out.pam"));
+    write_pam(&input, img);
+
+    let mut args = vec!["-c"];
+    args.extend(flags);
+    args.push(&input);
+    let compress = rpeg(&args);
+    assert!(compress.status.success(), "compress {:?} failed: {}", flags, String::from_utf8_lossy(&compress.stderr));
+    fs::write(&compressed, &compress.stdout).unwrap();
+
+    let decompress = rpeg(&["-d", &compressed, "-o", &output]);
+    assert!(decompress.status.success(), "decompress failed: {}", String::from_utf8_lossy(&decompress.stderr));
+    read_pam(&output)
+}
+
+/// Checks `bytes` against the golden file `name`, or rewrites the
+/// file when RPEG_BLESS is set
+pub fn check_golden(name: &str, bytes: &[u8]) {
+    let path = Path::new(FIXTURES).join(name);
+    if std::env::var_os("RPEG_BLESS").is_some() {
+        fs::create_dir_all(FIXTURES).unwrap();
+        fs::write(&path, bytes).unwrap();
+        return;
+    }
+
+    let expected = fs::read(&path).unwrap_or_else(|e| panic!("{}: {}, rerun with RPEG_BLESS=1", path.display(), e));
+    if let Some(offset) = expected.iter().zip(bytes).position(|(a, b)| a != b) {
+        panic!("{} differs at byte {}, rerun with RPEG_BLESS=1 if the change is intended", name, offset);
+    }
+    assert_eq!(expected.len(), bytes.len(), "{} changed length, rerun with RPEG_BLESS=1 if intended", name);
+}
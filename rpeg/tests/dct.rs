@@ -0,0 +1,94 @@
+mod common;
+
+use array2::Array2;
+use rpeg::block_packing::BlockLayout;
+use rpeg::codec::Vcs;
+use rpeg::compress_dct::{dct_on_block, dct_on_block_scaled, quantize_dct, transform_to_dct};
+use rpeg::curve::Curve;
+use rpeg::rdo::Quantizer;
+use rpeg::uncompress_dct::{undo_dct, undo_dct_on_block, undo_dct_on_block_scaled, undo_quantiza_dct};
+
+/// A 2x2 block of the given lumas, in row-major order
+fn block(lumas: [f32; 4]) -> Array2<Vcs> {
+    let pixels: Vec<Vcs> = lumas.iter().map(|&y| Vcs { y, pb: 0.0, pr: 0.0 }).collect();
+    Array2::from_row_major(2, 2, &pixels)
+}
+
+/// The lumas of a 2x2 block, in row-major order
+fn lumas(block: &Array2<Vcs>) -> Vec<f32> {
+    block.iter_row_major().map(|(_, _, pixel)| pixel.y).collect()
+}
+
+#[test]
+fn transform_is_inverted_exactly() {
+    for values in [[0.0, 0.0, 0.0, 0.0], [1.0, 1.0, 1.0, 1.0], [0.1, 0.9, 0.4, 0.6], [1.0, 0.0, 0.0, 1.0]] {
+        let (a, b, c, d) = transform_to_dct(&block(values));
+        for (got, want) in lumas(&undo_dct(a, b, c, d)).into_iter().zip(values) {
+            assert!((got - want).abs() < 1e-6, "{:?}", values);
+        }
+    }
+}
+
+#[test]
+fn flat_block_has_only_a_dc_term() {
+    assert_eq!(transform_to_dct(&block([0.25; 4])), (0.25, 0.0, 0.0, 0.0));
+}
+
+#[test]
+fn linear_quantization_error_is_at_most_half_a_step() {
+    for layout in BlockLayout::PROFILES {
+        let step = 0.3 / layout.max_level() as f32;
+        for i in -29..=29 {
+            let value = i as f32 / 100.0 + 0.003;
+            let (a, b, _, _) = quantize_dct(0.5, value, 0.0, 0.0, 1.0, Curve::Linear, &layout);
+            let (a, b, _, _) = undo_quantiza_dct(a, b, 0, 0, 1.0, Curve::Linear, &layout);
+            assert!((a - 0.5).abs() <= 0.5 / layout.a_scale() + 1e-6, "{}", layout.name);
+            assert!((b - value).abs() <= step / 2.0 + 1e-6, "{} {}", layout.name, value);
+        }
+    }
+}
+
+#[test]
+fn linear_quantization_clamps_large_coefficients() {
+    let (_, b, c, _) = quantize_dct(0.5, 0.45, -0.45, 0.0, 1.0, Curve::Linear, &BlockLayout::LEGACY);
+    assert_eq!((b, c), (15, -15));
+}
+
+#[test]
+fn companded_curves_reach_the_full_range() {
+    for curve in [Curve::Sqrt, Curve::Log] {
+        for layout in BlockLayout::PROFILES {
+            let top = layout.max_level();
+            assert_eq!(curve.quantize(0.5, top), top);
+            assert_eq!(curve.quantize(-0.5, top), -top);
+            assert_eq!(curve.quantize(0.0, top), 0);
+            assert!((curve.dequantize(top, top) - 0.5).abs() < 1e-5);
+        }
+    }
+}
+
+#[test]
+fn legacy_words_have_one_per_block() {
+    let img = common::to_vcs(&common::gradient(8, 6));
+    let words = dct_on_block(img, 6, 8);
+    assert_eq!(words.len(), 12);
+    assert_eq!(undo_dct_on_block(words, 6, 8).width(), 8);
+}
+
+#[test]
+fn block_luma_survives_every_layout() {
+    let original = common::to_vcs(&common::checkerboard(8, 8, 1));
+    for layout in BlockLayout::PROFILES {
+        for curve in [Curve::Linear, Curve::Sqrt, Curve::Log] {
+            let words: Vec<u64> = dct_on_block_scaled(original.clone(), 8, 8, None, Quantizer::Round, curve, &layout);
+            let decoded = undo_dct_on_block_scaled(words, 8, 8, None, curve, &layout);
+            let worst = original
+                .iter_row_major()
+                .zip(decoded.iter_row_major())
+                .map(|((_, _, a), (_, _, b))| (a.y - b.y).abs())
+                .fold(0.0, f32::max);
+            // The checkerboard's d of 0.25 is within the linear range, so only rounding is lost
+            assert!(worst < 0.02, "{} {:?}: {}", layout.name, curve, worst);
+        }
+    }
+}
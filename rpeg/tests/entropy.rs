@@ -0,0 +1,84 @@
+// Entropy coding of quantized coefficients with the coders in bitpack,
+// checking that the levels the codec produces survive them losslessly.
+
+mod common;
+
+use bitpack::codes::{unzigzag, zigzag};
+use bitpack::huffman::{HuffmanCode, HuffmanDecoder};
+use bitpack::range::{magnitude_context, IntModel, RangeDecoder, RangeEncoder};
+use bitpack::stream::{BitOrder, BitReader, BitWriter};
+use rpeg::block_packing::{unpack_blocks, BlockLayout};
+use rpeg::compress_dct::{dct_on_block_scaled, quantize_dct};
+use rpeg::curve::Curve;
+use rpeg::rdo::Quantizer;
+
+#[test]
+fn quantized_coefficients_round_trip_through_a_huffman_code() {
+    let layout = BlockLayout::HIGH;
+    let symbols: Vec<usize> = (0..500)
+        .flat_map(|i| {
+            let wave = (i as f32 * 0.37).sin() * 0.3;
+            let (_, b, c, d) = quantize_dct(0.5, wave, wave / 4.0, wave / 16.0, 1.0, Curve::Log, &layout);
+            [b, c, d].map(|level| zigzag(level) as usize)
+        })
+        .collect();
+
+    let mut frequencies = vec![0; zigzag(layout.max_level()) as usize + 1];
+    for &symbol in &symbols {
+        frequencies[symbol] += 1;
+    }
+    let code = HuffmanCode::from_frequencies(&frequencies, 15).unwrap();
+    let mut writer = BitWriter::new(Vec::new(), BitOrder::MsbFirst);
+    code.write_table(&mut writer).unwrap();
+    for &symbol in &symbols {
+        code.encode(&mut writer, symbol).unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+
+    // Small levels dominate, so the coded coefficients beat their 8-bit fields
+    assert!(bytes.len() < symbols.len());
+    let mut reader = BitReader::new(&bytes[..], BitOrder::MsbFirst);
+    let decoder = HuffmanDecoder::new(&HuffmanCode::read_table(&mut reader).unwrap());
+    for &symbol in &symbols {
+        let level = unzigzag(decoder.decode(&mut reader).unwrap() as u64);
+        assert_eq!(level, unzigzag(symbol as u64));
+        assert!(level.abs() <= layout.max_level());
+    }
+}
+
+#[test]
+fn coefficients_round_trip_through_a_context_modelled_range_coder() {
+    let (width, height) = (32, 32);
+    let layout = BlockLayout::HIGH;
+    let img = common::to_vcs(&common::gradient(width, height));
+    let words: Vec<u64> = dct_on_block_scaled(img, height, width, None, Quantizer::Round, Curve::Linear, &layout);
+    let [_, b, c, d, _, _] = unpack_blocks(&layout, &words);
+    let across = width / 2;
+
+    // Each coefficient is coded in the context of the same coefficient of the blocks to its left and above
+    let neighbours = |column: &[i64], index: usize| {
+        let left = if !index.is_multiple_of(across) { column[index - 1] } else { 0 };
+        let above = if index >= across { column[index - across] } else { 0 };
+        [left, above]
+    };
+    let mut models = vec![vec![IntModel::new(); 8]; 3];
+    let mut encoder = RangeEncoder::new(Vec::new());
+    for index in 0..words.len() {
+        for (column, models) in [&b, &c, &d].into_iter().zip(&mut models) {
+            let context = magnitude_context(&neighbours(column, index), models.len());
+            encoder.encode_int(&mut models[context], column[index]).unwrap();
+        }
+    }
+    let bytes = encoder.finish().unwrap();
+
+    // A smooth gradient codes to under a quarter of the 8-bit fields of its coefficients
+    assert!(bytes.len() * 4 < words.len() * 3, "{} bytes", bytes.len());
+    let mut models = vec![vec![IntModel::new(); 8]; 3];
+    let mut decoder = RangeDecoder::new(&bytes[..]).unwrap();
+    for index in 0..words.len() {
+        for (column, models) in [&b, &c, &d].into_iter().zip(&mut models) {
+            let context = magnitude_context(&neighbours(column, index), models.len());
+            assert_eq!(decoder.decode_int(&mut models[context]).unwrap(), column[index]);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// Golden files pin the exact bytes of each kind of rpeg file, so any change
+// to the bitstream or to quantization fails here. Rerun with RPEG_BLESS=1
+// to rewrite them after an intended change.
+// The chroma indices of the legacy layout come from the csc411 table rather
+// than from rpeg itself, so its luma fields are pinned on their own as well
+// as in a whole file.
+
+mod common;
+
+use array2::Array2;
+use rpeg::adaptive::{block_classes, pack_classes, SCALE_TAG};
+use rpeg::block_packing::BlockLayout;
+use rpeg::chunks::Chunk;
+use rpeg::codec::Vcs;
+use rpeg::compress_dct::dct_on_block;
+use rpeg::curve::{Curve, CURVE_TAG};
+use rpeg::delta::{decode_delta, encode_delta};
+use rpeg::rdo::Quantizer;
+use rpeg::tiling::{encode_tiles_scaled, read_tiled, write_delta, write_tiled};
+use rpeg::uncompress_pixels::vcs_to_rgb;
+
+use common::{check_golden, checkerboard, gradient, psnr, rpeg, to_vcs, write_pam, TempDir};
+
+/// Compresses `img` into a tiled file held in memory
+fn tiled_file(
+    img: &Array2<Vcs>,
+    tile_size: usize,
+    classes: Option<&Array2<u8>>,
+    quantizer: Quantizer,
+    curve: Curve,
+    layout: &BlockLayout,
+) -> Vec<u8> {
+    let tiles = encode_tiles_scaled(img, tile_size, classes, quantizer, curve, layout);
+    let mut chunks = Vec::new();
+    if let Some(classes) = classes {
+        chunks.push(Chunk { tag: SCALE_TAG, data: pack_classes(classes) });
+    }
+    if curve != Curve::Linear {
+        chunks.push(Chunk { tag: CURVE_TAG, data: curve.to_chunk_data() });
+    }
+
+    let mut bytes = Vec::new();
+    write_tiled(&mut bytes, img.width(), img.height(), tile_size, layout, &tiles, &chunks).unwrap();
+    bytes
+}
+
+/// Decodes a tiled file and checks it against the image it was made from
+fn check_decodes(name: &str, bytes: &[u8], original: &Array2<csc411_image::Rgb>, threshold: f64) -> Array2<Vcs> {
+    let decoded = read_tiled(bytes).unwrap().decode().unwrap();
+    let quality = psnr(original, &vcs_to_rgb(&decoded));
+    assert!(quality >= threshold, "{}: {:.1} dB is below {} dB", name, quality, threshold);
+    decoded
+}
+
+#[test]
+fn gradient_in_high_layout() {
+    let img = gradient(16, 12);
+    let bytes = tiled_file(&to_vcs(&img), 8, None, Quantizer::Round, Curve::Linear, &BlockLayout::HIGH);
+    check_golden("gradient_high.rpeg", &bytes);
+    check_decodes("gradient_high", &bytes, &img, 34.0);
+}
+
+#[test]
+fn checkerboard_in_near_lossless_layout_on_sqrt_curve() {
+    let img = checkerboard(12, 12, 3);
+    let bytes = tiled_file(&to_vcs(&img), 12, None, Quantizer::Round, Curve::Sqrt, &BlockLayout::NEAR_LOSSLESS);
+    check_golden("checker_near_lossless_sqrt.rpeg", &bytes);
+    check_decodes("checker_near_lossless_sqrt", &bytes, &img, 45.0);
+}
+
+#[test]
+fn adaptive_scales_with_rate_distortion() {
+    let img = gradient(16, 16);
+    let vcs = to_vcs(&img);
+    let classes = block_classes(&vcs);
+    let bytes = tiled_file(&vcs, 8, Some(&classes), Quantizer::RateDistortion(2.0), Curve::Log, &BlockLayout::HIGH);
+    check_golden("gradient_adaptive_rdo_log.rpeg", &bytes);
+    check_decodes("gradient_adaptive_rdo_log", &bytes, &img, 36.0);
+}
+
+#[test]
+fn residual_frame() {
+    let (first, second) = (gradient(16, 12), checkerboard(16, 12, 4));
+    let layout = BlockLayout::HIGH;
+    let key = tiled_file(&to_vcs(&first), 8, None, Quantizer::Round, Curve::Linear, &layout);
+    let previous = check_decodes("residual key", &key, &first, 34.0);
+
+    let tiles = encode_delta(&to_vcs(&second), &previous, 8, Quantizer::Round, Curve::Linear, &layout);
+    let mut bytes = Vec::new();
+    write_delta(&mut bytes, 16, 12, 8, &layout, &tiles, &[]).unwrap();
+    check_golden("residual_high.rpeg", &bytes);
+
+    let decoded = decode_delta(&read_tiled(&bytes).unwrap(), &previous).unwrap();
+    let quality = psnr(&second, &vcs_to_rgb(&decoded));
+    assert!(quality >= 36.0, "residual: {:.1} dB", quality);
+}
+
+#[test]
+fn legacy_luma_fields() {
+    // Only a, b, c and d are pinned, by clearing the chroma indices in the low byte
+    let img = gradient(16, 12);
+    let words = dct_on_block(to_vcs(&img), 12, 16);
+    let bytes: Vec<u8> = words.into_iter().flat_map(|word| (word & !0xFF).to_be_bytes()).collect();
+    check_golden("gradient_legacy_luma.bin", &bytes);
+}
+
+#[test]
+fn legacy_file() {
+    // The whole file as `rpeg -c` writes it, header and chroma indices included
+    let dir = TempDir::new("golden-legacy");
+    let input = dir.join("in.pam");
+    write_pam(&input, &gradient(16, 12));
+
+    let output = rpeg(&["-c", &input]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    check_golden("gradient_legacy.rpeg", &output.stdout);
+}
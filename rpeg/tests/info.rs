@@ -0,0 +1,33 @@
+mod common;
+
+use rpeg::block_packing::BlockLayout;
+use rpeg::codec::info;
+use rpeg::curve::Curve;
+use rpeg::delta::encode_delta;
+use rpeg::info::field_histograms;
+use rpeg::rdo::Quantizer;
+use rpeg::tiling::write_delta;
+
+use common::{flat, to_vcs, TempDir};
+
+#[test]
+fn residual_tiles_are_counted_with_a_signed_a() {
+    let dir = TempDir::new("info-residual");
+    let layout = BlockLayout::LEGACY;
+    let previous = to_vcs(&flat(8, 8, 200, 200, 200));
+    let current = to_vcs(&flat(8, 8, 100, 100, 100));
+
+    // Every block got darker, so every residual `a` is negative
+    let tiles = encode_delta(&current, &previous, 8, Quantizer::Round, Curve::Linear, &layout);
+    let mut bytes = Vec::new();
+    write_delta(&mut bytes, 8, 8, 8, &layout, &tiles, &[]).unwrap();
+    let path = dir.join("frame.rpeg");
+    std::fs::write(&path, bytes).unwrap();
+
+    let info = info(&path).unwrap();
+    assert_eq!(info.words.len(), 16);
+    let (histograms, skipped) = field_histograms(&info.words, &info.layout);
+    assert_eq!(skipped, 0);
+    assert_eq!(histograms[0].counts.values().sum::<usize>(), 16);
+    assert!(histograms[0].counts.keys().all(|&a| a < 0), "{:?}", histograms[0]);
+}
@@ -0,0 +1,82 @@
+use std::io::Cursor;
+
+use rpeg::adaptive::SCALE_TAG;
+use rpeg::block_packing::{BlockLayout, Field};
+use rpeg::chunks::Chunk;
+use rpeg::codec::decode_legacy;
+use rpeg::curve::{Curve, CURVE_TAG};
+use rpeg::integrity::MAX_PIXELS;
+use rpeg::sequence::Sequence;
+use rpeg::tiling::{read_tiled, write_tiled};
+
+/// A legacy file with the given header numbers and payload
+fn legacy(dimensions: &str, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = format!("Compressed image format 2\n{}\n", dimensions).into_bytes();
+    bytes.extend(payload);
+    bytes
+}
+
+#[test]
+fn legacy_header_must_be_well_formed() {
+    for dimensions in ["", "4", "4 4 4", "4 x", "-4 4", "3 4", "4 5"] {
+        assert!(decode_legacy(&legacy(dimensions, &[0; 16])).is_err(), "{:?}", dimensions);
+    }
+    assert!(decode_legacy(b"Compressed image format 2").is_err());
+    assert!(decode_legacy(b"P6\n4 4\n255\n").is_err());
+}
+
+#[test]
+fn legacy_dimensions_are_limited() {
+    let huge = format!("{} 2", MAX_PIXELS);
+    assert!(decode_legacy(&legacy(&huge, &[])).is_err());
+    let overflowing = format!("{} {}", usize::MAX - 1, usize::MAX - 1);
+    assert!(decode_legacy(&legacy(&overflowing, &[])).is_err());
+}
+
+#[test]
+fn truncated_legacy_payload_is_damage() {
+    // Four blocks are expected but only one whole word is present
+    let (image, damage) = decode_legacy(&legacy("4 4", &[0; 6])).unwrap();
+    assert_eq!((image.width(), image.height()), (4, 4));
+    let damage = damage.unwrap();
+    assert_eq!((damage.y, damage.height), (0, 4));
+}
+
+#[test]
+fn tiled_dimensions_are_limited() {
+    let header = format!("Compressed tiled image format 3\n{} 2 {} 1\n", MAX_PIXELS, MAX_PIXELS);
+    let mut bytes = header.into_bytes();
+    bytes.extend([0; 12]);
+    assert!(read_tiled(&bytes).is_err());
+}
+
+#[test]
+fn truncated_sequence_is_an_error() {
+    let mut bytes = b"Compressed image sequence format 1\n".to_vec();
+    bytes.extend([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 2, 0xFF, 0xFF, 0xFF, 0xFF]);
+    assert!(Sequence::open(Cursor::new(bytes)).is_err());
+}
+
+#[test]
+fn chroma_indices_past_the_field_are_clamped() {
+    let field = Field::unsigned(4, 0);
+    assert_eq!(field.chroma_value(u64::MAX), field.chroma_value(15));
+}
+
+#[test]
+fn damaged_scale_and_curve_chunks_fall_back_to_the_defaults() {
+    let tiles = vec![vec![0u64; 4]];
+    for tag in [SCALE_TAG, CURVE_TAG] {
+        let chunks = [Chunk { tag, data: vec![9, 9, 9] }];
+        let mut bytes = Vec::new();
+        write_tiled(&mut bytes, 4, 4, 4, &BlockLayout::LEGACY, &tiles, &chunks).unwrap();
+
+        // The file still opens, the damage is recorded and the blocks decode with the defaults
+        let tiled = read_tiled(&bytes).unwrap();
+        assert!(tiled.chunk_error.is_some(), "{:?}", tag);
+        assert!(tiled.classes.is_none());
+        assert_eq!(tiled.curve, Curve::Linear);
+        assert_eq!(tiled.verify().len(), 1);
+        assert!(tiled.decode_recovering().1.is_empty());
+    }
+}